@@ -6,24 +6,12 @@ pub struct Counter {
 }
 
 impl Counter {
-    pub const fn new() -> Self {
-        Self {
-            count: Cell::new(0),
-        }
-    }
-
     pub fn increment(&self) {
         let next = self.count.get() + 1;
         self.count.set(next);
     }
 
-    pub fn get(&self) -> usize {
-        self.count.get()
-    }
-
-    pub fn wrapping_add_signed(&self, rhs: isize) -> usize {
-        let count = self.count.get() as isize;
-        let result = count.wrapping_add(rhs);
-        result as usize
+    pub const fn wrapping_add_signed(&self, rhs: isize) -> usize {
+        self.count.get().wrapping_add_signed(rhs)
     }
 }
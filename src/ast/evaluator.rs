@@ -1,58 +1,1464 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::diagnostics::BagCell;
+
 use super::{
-    lexer::TextSpan, BinaryExpression, BinaryOperatorKind, Expression, ExpressionKind,
-    NumberExpression, Statement, StatementKind, Visitor,
+    lexer::{TextSpan, Token},
+    parser::STACK_SIZE,
+    AssignmentStatement, Ast, BinaryExpression, BinaryOperator, BinaryOperatorKind,
+    BlockExpression, BooleanExpression, BreakStatement, CallExpression, ClosureExpression,
+    ContinueStatement, Expression, ExpressionKind, FloatExpression, ForStatement,
+    FunctionDeclaration, IfExpression, LetStatement, NumberExpression, ReturnStatement, Statement,
+    StatementKind, StringExpression, UnaryExpression, UnaryOperatorKind, VariableExpression,
+    Visitor, WhileStatement,
 };
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    /// The integers from `start` up to `end`, including `end` if the range is inclusive.
+    Range {
+        start: i64,
+        end: i64,
+        is_inclusive: bool,
+    },
+    Function(Rc<Function>),
+    /// The value of a block that does not end in an expression.
+    Unit,
+}
+
+/// A function declared with `fn`, or a closure, which has no name. Calling it
+/// runs `body` in a new frame holding `captured` and then the arguments.
+///
+/// Both capture the local variables in scope when they are created by value:
+/// assignments made after that, whether by the function or around it, are not
+/// seen by the other side, and each call starts from the same copy. Globals
+/// are not captured, so every function shares them, and a closure bound to a
+/// global with `let` can call itself through it. One bound to a local cannot,
+/// since the local does not exist yet when the closure captures its scope.
+///
+/// A function declared in a block is created when its declaration runs, so
+/// it can call the functions declared before it in the block but not those
+/// after, which do not exist yet. Top-level functions are globals declared
+/// before the program runs, so they can call each other in any order.
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    pub body: Rc<Expression>,
+    pub captured: Vec<Scope>,
+}
+
+impl Value {
+    pub const fn type_name(&self) -> &'static str {
+        match self {
+            Self::Int(_) => "int",
+            Self::Float(_) => "float",
+            Self::Bool(_) => "bool",
+            Self::String(_) => "string",
+            Self::Range { .. } => "range",
+            Self::Function(_) => "function",
+            Self::Unit => "()",
+        }
+    }
+
+    /// Numbers as a float, used when an int is mixed with a float. Ints beyond
+    /// 2^53 lose precision in the promotion.
+    #[allow(clippy::cast_precision_loss)]
+    const fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Int(value) => Some(*value as f64),
+            Self::Float(value) => Some(*value),
+            Self::Bool(_)
+            | Self::String(_)
+            | Self::Range { .. }
+            | Self::Function(_)
+            | Self::Unit => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{value}"),
+            // Debug formatting keeps the `.0` on whole floats so they read differently to ints
+            Self::Float(value) => write!(f, "{value:?}"),
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+            Self::Range {
+                start,
+                end,
+                is_inclusive,
+            } => {
+                let operator = if *is_inclusive { "..=" } else { ".." };
+                write!(f, "{start}{operator}{end}")
+            }
+            Self::Function(function) => match &function.name {
+                Some(name) => write!(f, "<fn {name}>"),
+                None => write!(f, "<closure>"),
+            },
+            Self::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// Why applying an operator to its operands did not produce a value. Reported
+/// against the span of the operator's token.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OperatorError {
+    InvalidOperand(&'static str),
+    InvalidOperands(&'static str, &'static str),
+    DivisionByZero,
+    ModuloByZero,
+    Overflow,
+    ShiftOutOfRange(i64),
+}
+
+type Scope = HashMap<String, Value>;
+
+/// Variables in scope, with one map for each block being evaluated. The first
+/// scope holds the globals.
+pub struct Environment {
+    scopes: Vec<Scope>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+        }
+    }
+}
+
+impl Environment {
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Drop every variable declared since the matching `push_scope`.
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Start a call frame. Only the global scope stays visible, below copies
+    /// of `captured` and a new scope for the call, until `exit_frame` is given
+    /// back the caller's scopes.
+    pub fn enter_frame(&mut self, captured: &[Scope]) -> Vec<Scope> {
+        let caller_scopes = self.scopes.split_off(1);
+        self.scopes.extend_from_slice(captured);
+        self.push_scope();
+        caller_scopes
+    }
+
+    /// Copy every scope but the global one, for a closure to take with it.
+    pub fn capture(&self) -> Vec<Scope> {
+        self.scopes[1..].to_vec()
+    }
+
+    pub fn exit_frame(&mut self, caller_scopes: Vec<Scope>) {
+        self.scopes.truncate(1);
+        self.scopes.extend(caller_scopes);
+    }
+
+    /// Declare a variable in the innermost scope, shadowing any outer one with the same name.
+    pub fn declare(&mut self, name: &str, value: Value) {
+        self.scopes
+            .last_mut()
+            .expect("The global scope is never popped")
+            .insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Replace the value of the innermost variable called `name`, returning
+    /// `false` if there is none.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        let variable = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name));
+
+        variable.map(|variable| *variable = value).is_some()
+    }
+}
+
+/// A jump out of the statements being evaluated, carried up to the loop or
+/// call that handles it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ControlFlow {
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// How deeply calls may nest before a "stack overflow" is reported.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 500;
+
+/// The most calls may be allowed to nest, which keeps the stack asked for by
+/// `stack_size` within reason.
+pub const MAX_CALL_DEPTH: usize = 5_000;
+
+/// How deeply statements and expressions may nest, on average, in each call.
+/// Every level recurses on the host stack, so a "stack overflow" is also
+/// reported once calls and the code in them nest deeper than the maximum call
+/// depth times this, however few calls that took.
+const DEPTH_PER_CALL: usize = 64;
+
+/// The host stack each level of evaluation needs, with room to spare in a
+/// debug build.
+const STACK_PER_LEVEL: usize = 1024;
+
+/// The host stack needed to run a program with calls nested up to
+/// `max_call_depth` deep. The code in the innermost call can still be as deep
+/// as the parser allows, so this comes on top of what the parser needs.
+pub const fn stack_size(max_call_depth: usize) -> usize {
+    STACK_SIZE + max_call_depth * DEPTH_PER_CALL * STACK_PER_LEVEL
+}
+
+/// `last_value` is `None` once a runtime error has been reported, and every
+/// statement after that is skipped. It is also `None` while `control_flow`
+/// unwinds to the enclosing loop or call, so the expressions around a `break`
+/// or `return` stop evaluating the same way they do for an error.
 #[derive(Default)]
 pub struct Evaluator {
-    pub last_value: Option<i64>,
-    pub values: Vec<i64>,
+    pub last_value: Option<Value>,
+    pub environment: Environment,
+    control_flow: Option<ControlFlow>,
+    call_depth: usize,
+    max_call_depth: usize,
+    /// How deeply statements and expressions are nested, across every call.
+    depth: usize,
+    bag: BagCell,
+}
+
+impl Evaluator {
+    pub fn new(bag: BagCell) -> Self {
+        Self {
+            bag,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            ..Self::default()
+        }
+    }
+
+    pub const fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    /// Evaluate a whole program, declaring its top-level functions before
+    /// running any statement, as `Resolver::resolve` expects. Like every
+    /// statement, nothing is evaluated once an error has been reported.
+    pub fn evaluate(&mut self, ast: &Ast) {
+        if self.bag.borrow().has_errors() {
+            return;
+        }
+
+        for function in ast.functions() {
+            self.visit_function_declaration(function);
+        }
+
+        ast.visit(self);
+    }
+
+    /// Whether a loop whose body has just run goes round again. A `break` or
+    /// `continue` is handled here, while a `return` or an error gives `None`
+    /// to leave the loop without a value.
+    fn end_iteration(&mut self) -> Option<bool> {
+        match self.control_flow {
+            Some(ControlFlow::Break) => {
+                self.control_flow = None;
+                Some(false)
+            }
+            Some(ControlFlow::Continue) => {
+                self.control_flow = None;
+                Some(true)
+            }
+            Some(ControlFlow::Return(_)) => None,
+            None => self.last_value.is_some().then_some(true),
+        }
+    }
+
+    /// Call `callee` in a new frame, returning `None` if the call reported an error.
+    fn call(&mut self, callee: &Value, arguments: Vec<Value>, span: &TextSpan) -> Option<Value> {
+        let Value::Function(function) = callee else {
+            self.bag
+                .borrow_mut()
+                .report_not_callable(span.clone(), callee.type_name());
+            return None;
+        };
+
+        if arguments.len() != function.parameters.len() {
+            self.bag.borrow_mut().report_arity_mismatch(
+                span.clone(),
+                function.parameters.len(),
+                arguments.len(),
+            );
+            return None;
+        }
+
+        if self.call_depth >= self.max_call_depth {
+            self.bag
+                .borrow_mut()
+                .report_stack_overflow(span.clone(), self.max_call_depth);
+            return None;
+        }
+
+        let max_depth = self.max_call_depth * DEPTH_PER_CALL;
+        if self.depth >= max_depth {
+            self.bag
+                .borrow_mut()
+                .report_nested_too_deep_in_calls(span.clone(), max_depth);
+            return None;
+        }
+
+        self.call_depth += 1;
+        let caller_scopes = self.environment.enter_frame(&function.captured);
+
+        // The function can call itself even if it was declared in a block the frame cannot see
+        if let Some(name) = &function.name {
+            self.environment.declare(name, callee.clone());
+        }
+
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            self.environment.declare(parameter, argument);
+        }
+
+        self.visit_expression(&function.body);
+
+        self.environment.exit_frame(caller_scopes);
+        self.call_depth -= 1;
+
+        match self.control_flow.take() {
+            Some(ControlFlow::Return(value)) => Some(value),
+            _ => self.last_value.take(),
+        }
+    }
+
+    /// Unwrap the result of an operator, reporting the error against `operator` if there is one.
+    fn check_operator(
+        &self,
+        operator: &Token,
+        result: Result<Value, OperatorError>,
+    ) -> Option<Value> {
+        result
+            .map_err(|error| {
+                self.bag
+                    .borrow_mut()
+                    .report_operator_error(operator, &error);
+            })
+            .ok()
+    }
+
+    fn apply_binary_operator(
+        &self,
+        operator: &BinaryOperator,
+        left: &Value,
+        right: &Value,
+    ) -> Option<Value> {
+        let result = match (left, right) {
+            (Value::Int(left), Value::Int(right)) => {
+                Self::apply_integer_operator(&operator.kind, *left, *right)
+            }
+            (Value::Bool(left), Value::Bool(right)) => {
+                Self::apply_boolean_operator(&operator.kind, *left, *right)
+            }
+            (Value::String(left), Value::String(right)) => {
+                Self::apply_string_operator(&operator.kind, left, right)
+            }
+            _ => match (left.as_float(), right.as_float()) {
+                (Some(left), Some(right)) => {
+                    Self::apply_float_operator(&operator.kind, left, right)
+                }
+                _ => None,
+            }
+            .ok_or_else(|| OperatorError::InvalidOperands(left.type_name(), right.type_name())),
+        };
+
+        self.check_operator(&operator.token, result)
+    }
+
+    /// Follows IEEE 754, so dividing by zero gives an infinity or NaN rather
+    /// than an error. `%` and the bitwise operators are not defined for floats.
+    #[allow(clippy::float_cmp)]
+    const fn apply_float_operator(
+        kind: &BinaryOperatorKind,
+        left: f64,
+        right: f64,
+    ) -> Option<Value> {
+        let value = match kind {
+            BinaryOperatorKind::Add => Value::Float(left + right),
+            BinaryOperatorKind::Subtract => Value::Float(left - right),
+            BinaryOperatorKind::Multiply => Value::Float(left * right),
+            BinaryOperatorKind::Divide => Value::Float(left / right),
+            BinaryOperatorKind::Equals => Value::Bool(left == right),
+            BinaryOperatorKind::NotEquals => Value::Bool(left != right),
+            BinaryOperatorKind::LessThan => Value::Bool(left < right),
+            BinaryOperatorKind::LessThanOrEquals => Value::Bool(left <= right),
+            BinaryOperatorKind::GreaterThan => Value::Bool(left > right),
+            BinaryOperatorKind::GreaterThanOrEquals => Value::Bool(left >= right),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    fn apply_integer_operator(
+        kind: &BinaryOperatorKind,
+        left: i64,
+        right: i64,
+    ) -> Result<Value, OperatorError> {
+        let int = |value: Option<i64>| value.map(Value::Int).ok_or(OperatorError::Overflow);
+        let shift_amount = || {
+            u32::try_from(right)
+                .ok()
+                .filter(|amount| *amount < i64::BITS)
+                .ok_or(OperatorError::ShiftOutOfRange(right))
+        };
+
+        match kind {
+            BinaryOperatorKind::Add => int(left.checked_add(right)),
+            BinaryOperatorKind::Subtract => int(left.checked_sub(right)),
+            BinaryOperatorKind::Multiply => int(left.checked_mul(right)),
+            BinaryOperatorKind::Divide if right == 0 => Err(OperatorError::DivisionByZero),
+            BinaryOperatorKind::Divide => int(left.checked_div(right)),
+            BinaryOperatorKind::Mod if right == 0 => Err(OperatorError::ModuloByZero),
+            BinaryOperatorKind::Mod => int(left.checked_rem(right)),
+            BinaryOperatorKind::Equals => Ok(Value::Bool(left == right)),
+            BinaryOperatorKind::NotEquals => Ok(Value::Bool(left != right)),
+            BinaryOperatorKind::LessThan => Ok(Value::Bool(left < right)),
+            BinaryOperatorKind::LessThanOrEquals => Ok(Value::Bool(left <= right)),
+            BinaryOperatorKind::GreaterThan => Ok(Value::Bool(left > right)),
+            BinaryOperatorKind::GreaterThanOrEquals => Ok(Value::Bool(left >= right)),
+            BinaryOperatorKind::BitwiseAnd => Ok(Value::Int(left & right)),
+            BinaryOperatorKind::BitwiseOr => Ok(Value::Int(left | right)),
+            BinaryOperatorKind::BitwiseXor => Ok(Value::Int(left ^ right)),
+            // Bits shifted out are discarded, only the amount itself is checked
+            BinaryOperatorKind::ShiftLeft => Ok(Value::Int(left << shift_amount()?)),
+            BinaryOperatorKind::ShiftRight => Ok(Value::Int(left >> shift_amount()?)),
+            BinaryOperatorKind::Range => Ok(Value::Range {
+                start: left,
+                end: right,
+                is_inclusive: false,
+            }),
+            BinaryOperatorKind::RangeInclusive => Ok(Value::Range {
+                start: left,
+                end: right,
+                is_inclusive: true,
+            }),
+            BinaryOperatorKind::And | BinaryOperatorKind::Or => {
+                Err(OperatorError::InvalidOperands("int", "int"))
+            }
+        }
+    }
+
+    const fn apply_boolean_operator(
+        kind: &BinaryOperatorKind,
+        left: bool,
+        right: bool,
+    ) -> Result<Value, OperatorError> {
+        match kind {
+            BinaryOperatorKind::Equals => Ok(Value::Bool(left == right)),
+            BinaryOperatorKind::NotEquals => Ok(Value::Bool(left != right)),
+            BinaryOperatorKind::And => Ok(Value::Bool(left && right)),
+            BinaryOperatorKind::Or => Ok(Value::Bool(left || right)),
+            BinaryOperatorKind::BitwiseAnd => Ok(Value::Bool(left & right)),
+            BinaryOperatorKind::BitwiseOr => Ok(Value::Bool(left | right)),
+            BinaryOperatorKind::BitwiseXor => Ok(Value::Bool(left ^ right)),
+            _ => Err(OperatorError::InvalidOperands("bool", "bool")),
+        }
+    }
+
+    fn apply_string_operator(
+        kind: &BinaryOperatorKind,
+        left: &str,
+        right: &str,
+    ) -> Result<Value, OperatorError> {
+        match kind {
+            BinaryOperatorKind::Add => Ok(Value::String(format!("{left}{right}"))),
+            BinaryOperatorKind::Equals => Ok(Value::Bool(left == right)),
+            BinaryOperatorKind::NotEquals => Ok(Value::Bool(left != right)),
+            _ => Err(OperatorError::InvalidOperands("string", "string")),
+        }
+    }
+
+    /// Evaluate `&&` or `||` given an already evaluated left operand. The right
+    /// operand is only visited when the left one does not decide the result.
+    fn apply_short_circuit_operator(
+        &mut self,
+        operator: &BinaryOperator,
+        left: &Value,
+        right: &Expression,
+    ) -> Option<Value> {
+        let &Value::Bool(left) = left else {
+            let error = OperatorError::InvalidOperand(left.type_name());
+            return self.check_operator(&operator.token, Err(error));
+        };
+
+        // `true || ...` and `false && ...` are decided by the left operand alone
+        if left == (operator.kind == BinaryOperatorKind::Or) {
+            return Some(Value::Bool(left));
+        }
+
+        self.visit_expression(right);
+
+        match self.last_value.take()? {
+            Value::Bool(right) => Some(Value::Bool(right)),
+            right @ (Value::Int(_)
+            | Value::Float(_)
+            | Value::String(_)
+            | Value::Range { .. }
+            | Value::Function(_)
+            | Value::Unit) => {
+                let error = OperatorError::InvalidOperand(right.type_name());
+                self.check_operator(&operator.token, Err(error))
+            }
+        }
+    }
 }
 
 impl Visitor for Evaluator {
+    fn visit_statement(&mut self, statement: &Statement) {
+        if self.bag.borrow().has_errors() {
+            return;
+        }
+
+        self.depth += 1;
+        match &statement.kind {
+            StatementKind::Expression(expr) => self.visit_expression(expr),
+            StatementKind::Let(statement) => self.visit_let_statement(statement),
+            StatementKind::Assignment(statement) => self.visit_assignment_statement(statement),
+            StatementKind::Block(block) => self.visit_block_expression(block),
+            StatementKind::While(statement) => self.visit_while_statement(statement),
+            StatementKind::For(statement) => self.visit_for_statement(statement),
+            StatementKind::Function(statement) => self.visit_function_declaration(statement),
+            StatementKind::Return(statement) => self.visit_return_statement(statement),
+            StatementKind::Break(statement) => self.visit_break_statement(statement),
+            StatementKind::Continue(statement) => self.visit_continue_statement(statement),
+        }
+        self.depth -= 1;
+    }
+
+    fn visit_while_statement(&mut self, statement: &WhileStatement) {
+        loop {
+            self.visit_expression(&statement.condition);
+
+            let Some(condition) = self.last_value.take() else {
+                return;
+            };
+
+            let Value::Bool(condition) = condition else {
+                self.bag
+                    .borrow_mut()
+                    .report_non_bool_condition(&statement.while_keyword, condition.type_name());
+                return;
+            };
+
+            if !condition {
+                break;
+            }
+
+            self.visit_block_expression(&statement.body);
+
+            match self.end_iteration() {
+                Some(true) => {}
+                Some(false) => break,
+                None => return,
+            }
+        }
+
+        self.last_value = Some(Value::Unit);
+    }
+
+    fn visit_for_statement(&mut self, statement: &ForStatement) {
+        self.visit_expression(&statement.iterable);
+
+        let Some(iterable) = self.last_value.take() else {
+            return;
+        };
+
+        let Value::Range {
+            start,
+            end,
+            is_inclusive,
+        } = iterable
+        else {
+            self.bag
+                .borrow_mut()
+                .report_not_iterable(&statement.for_keyword, iterable.type_name());
+            return;
+        };
+
+        // Stepping with `checked_add` ends an inclusive range at `i64::MAX` without overflowing
+        let mut next = Some(start);
+
+        while let Some(value) = next.filter(|value| *value < end || is_inclusive && *value == end) {
+            self.environment.push_scope();
+            self.environment
+                .declare(statement.name(), Value::Int(value));
+            self.visit_block_expression(&statement.body);
+            self.environment.pop_scope();
+
+            match self.end_iteration() {
+                Some(true) => {}
+                Some(false) => break,
+                None => return,
+            }
+
+            next = value.checked_add(1);
+        }
+
+        self.last_value = Some(Value::Unit);
+    }
+
+    fn visit_function_declaration(&mut self, statement: &FunctionDeclaration) {
+        let function = Value::Function(Rc::new(Function {
+            name: Some(statement.name().to_string()),
+            parameters: statement
+                .parameters
+                .iter()
+                .map(|parameter| parameter.span.literal.clone())
+                .collect(),
+            body: Rc::clone(&statement.body),
+            captured: self.environment.capture(),
+        }));
+
+        self.environment.declare(statement.name(), function.clone());
+        self.last_value = Some(function);
+    }
+
+    fn visit_return_statement(&mut self, statement: &ReturnStatement) {
+        let value = match &statement.value {
+            Some(value) => {
+                self.visit_expression(value);
+
+                let Some(value) = self.last_value.take() else {
+                    return;
+                };
+
+                value
+            }
+            None => Value::Unit,
+        };
+
+        self.control_flow = Some(ControlFlow::Return(value));
+        self.last_value = None;
+    }
+
+    fn visit_break_statement(&mut self, _statement: &BreakStatement) {
+        self.control_flow = Some(ControlFlow::Break);
+        self.last_value = None;
+    }
+
+    fn visit_continue_statement(&mut self, _statement: &ContinueStatement) {
+        self.control_flow = Some(ControlFlow::Continue);
+        self.last_value = None;
+    }
+
+    fn visit_let_statement(&mut self, statement: &LetStatement) {
+        self.visit_expression(&statement.initializer);
+
+        if let Some(value) = &self.last_value {
+            self.environment.declare(statement.name(), value.clone());
+        }
+    }
+
+    fn visit_assignment_statement(&mut self, statement: &AssignmentStatement) {
+        self.visit_expression(&statement.value);
+
+        let Some(value) = self.last_value.take() else {
+            return;
+        };
+
+        // As with reading, a function called before the `let` ran gets here
+        let Some(current) = self.environment.get(statement.name()) else {
+            self.bag
+                .borrow_mut()
+                .report_assigned_before_let(&statement.identifier);
+            return;
+        };
+
+        let value = match statement.operator.binary_operator() {
+            Some(operator) => self.apply_binary_operator(&operator, current, &value),
+            None => Some(value),
+        };
+
+        if let Some(value) = &value {
+            self.environment.assign(statement.name(), value.clone());
+        }
+
+        self.last_value = value;
+    }
+
     fn visit_number(&mut self, number: &NumberExpression) {
-        self.last_value = Some(number.number);
+        self.last_value = Some(Value::Int(number.number));
+    }
+
+    fn visit_float(&mut self, float: &FloatExpression) {
+        self.last_value = Some(Value::Float(float.number));
+    }
+
+    fn visit_string(&mut self, string: &StringExpression) {
+        self.last_value = Some(Value::String(string.value.clone()));
+    }
+
+    fn visit_boolean(&mut self, boolean: &BooleanExpression) {
+        self.last_value = Some(Value::Bool(boolean.value));
+    }
+
+    fn visit_unary_expression(&mut self, expr: &UnaryExpression) {
+        self.visit_expression(&expr.operand);
+
+        let Some(operand) = self.last_value.take() else {
+            return;
+        };
+
+        let result = match (&expr.operator.kind, operand) {
+            (UnaryOperatorKind::Negate, Value::Int(operand)) => operand
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or(OperatorError::Overflow),
+            (UnaryOperatorKind::Negate, Value::Float(operand)) => Ok(Value::Float(-operand)),
+            (UnaryOperatorKind::Identity, operand @ (Value::Int(_) | Value::Float(_))) => {
+                Ok(operand)
+            }
+            (UnaryOperatorKind::Not, Value::Bool(operand)) => Ok(Value::Bool(!operand)),
+            (UnaryOperatorKind::BitwiseNot, Value::Int(operand)) => Ok(Value::Int(!operand)),
+            (_, operand) => Err(OperatorError::InvalidOperand(operand.type_name())),
+        };
+
+        self.last_value = self.check_operator(&expr.operator.token, result);
     }
 
     fn visit_binary_expression(&mut self, expr: &BinaryExpression) {
         self.visit_expression(&expr.left);
-        let left = self.last_value.expect("Don't care");
-        self.visit_expression(&expr.right);
-        let right = self.last_value.expect("Don't care");
+        let Some(left) = self.last_value.take() else {
+            return;
+        };
 
-        self.last_value = match expr.operator.kind {
-            BinaryOperatorKind::Add => Some(left + right),
-            BinaryOperatorKind::Subtract => Some(left - right),
-            BinaryOperatorKind::Multiply => Some(left * right),
-            BinaryOperatorKind::Divide => Some(left / right),
-            BinaryOperatorKind::Mod => Some(left % right),
+        if expr.operator.kind.is_short_circuiting() {
+            self.last_value = self.apply_short_circuit_operator(&expr.operator, &left, &expr.right);
+            return;
+        }
+
+        self.visit_expression(&expr.right);
+        let Some(right) = self.last_value.take() else {
+            return;
         };
+
+        self.last_value = self.apply_binary_operator(&expr.operator, &left, &right);
     }
 
     fn visit_parenthesized_expression(&mut self, expr: &super::ParenthesizedExpression) {
         self.visit_expression(expr.expression.as_ref());
     }
 
-    // fn visit_statement(&mut self, statement: &Statement) {
-    //     match &statement.kind {
-    //         StatementKind::Expression(expression) => self.visit_expression(expression),
-    //     }
+    fn visit_variable_expression(&mut self, expr: &VariableExpression) {
+        self.last_value = self.environment.get(expr.name()).cloned();
+
+        // The resolver has seen the `let`, but a function called before it
+        // ran can still get here first
+        if self.last_value.is_none() {
+            self.bag
+                .borrow_mut()
+                .report_used_before_let(&expr.identifier);
+        }
+    }
+
+    fn visit_block_expression(&mut self, block: &BlockExpression) {
+        self.environment.push_scope();
+        self.last_value = Some(Value::Unit);
+
+        for statement in &block.statements {
+            self.visit_statement(statement);
+
+            if self.control_flow.is_some() {
+                break;
+            }
+        }
+
+        let ends_in_expression = matches!(
+            block.statements.last(),
+            Some(Statement {
+                kind: StatementKind::Expression(_)
+            })
+        );
+
+        if !ends_in_expression && self.last_value.is_some() {
+            self.last_value = Some(Value::Unit);
+        }
+
+        self.environment.pop_scope();
+    }
+
+    fn visit_call_expression(&mut self, expr: &CallExpression) {
+        self.visit_expression(&expr.callee);
+
+        let Some(callee) = self.last_value.take() else {
+            return;
+        };
+
+        let mut arguments = Vec::with_capacity(expr.arguments.len());
+
+        for argument in &expr.arguments {
+            self.visit_expression(argument);
 
-    //     if let Some(result) = self.last_value {
-    //         self.values.push(result);
-    //     }
-    // }
+            let Some(argument) = self.last_value.take() else {
+                return;
+            };
+
+            arguments.push(argument);
+        }
+
+        self.last_value = self.call(&callee, arguments, &expr.span);
+    }
+
+    fn visit_closure_expression(&mut self, expr: &ClosureExpression) {
+        self.last_value = Some(Value::Function(Rc::new(Function {
+            name: None,
+            parameters: expr
+                .parameters
+                .iter()
+                .map(|parameter| parameter.span.literal.clone())
+                .collect(),
+            body: Rc::clone(&expr.body),
+            captured: self.environment.capture(),
+        })));
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        self.visit_expression(&expr.condition);
+
+        let Some(condition) = self.last_value.take() else {
+            return;
+        };
+
+        let Value::Bool(condition) = condition else {
+            self.bag
+                .borrow_mut()
+                .report_non_bool_condition(&expr.if_keyword, condition.type_name());
+            return;
+        };
+
+        if condition {
+            self.visit_block_expression(&expr.then_branch);
+        } else if let Some(else_branch) = &expr.else_branch {
+            self.visit_expression(&else_branch.body);
+        } else {
+            self.last_value = Some(Value::Unit);
+        }
+    }
 
     fn visit_expression(&mut self, expression: &Expression) {
+        self.depth += 1;
         match &expression.kind {
             ExpressionKind::Number(number) => self.visit_number(number),
+            ExpressionKind::Float(float) => self.visit_float(float),
+            ExpressionKind::String(string) => self.visit_string(string),
+            ExpressionKind::Boolean(boolean) => self.visit_boolean(boolean),
+            ExpressionKind::Unary(expr) => self.visit_unary_expression(expr),
             ExpressionKind::Binary(expr) => self.visit_binary_expression(expr),
             ExpressionKind::Parenthesized(expr) => self.visit_parenthesized_expression(expr),
+            ExpressionKind::Variable(expr) => self.visit_variable_expression(expr),
+            ExpressionKind::Block(block) => self.visit_block_expression(block),
+            ExpressionKind::If(expr) => self.visit_if_expression(expr),
+            ExpressionKind::Call(expr) => self.visit_call_expression(expr),
+            ExpressionKind::Closure(expr) => self.visit_closure_expression(expr),
             ExpressionKind::Error(span) => self.visit_error(span),
         }
+        self.depth -= 1;
+    }
+
+    // Unreachable: a program with syntax errors is never evaluated
+    fn visit_error(&mut self, _span: &TextSpan) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{stack_size, Evaluator, Value, DEFAULT_MAX_CALL_DEPTH};
+    use crate::ast::lexer::TextSpan;
+    use crate::ast::resolver::Resolver;
+    use crate::ast::test_helpers::{messages, new_bag, parse_ast, take_diagnostics, with_stack};
+    use crate::diagnostics::Diagnostic;
+
+    /// Run `input` through the same passes as `main`, returning the value of
+    /// the last statement and every diagnostic reported along the way.
+    fn evaluate(input: &str) -> (Option<Value>, Vec<Diagnostic>) {
+        let bag = new_bag();
+        let ast = parse_ast(input, &bag);
+
+        Resolver::new(Rc::clone(&bag)).resolve(&ast);
+
+        let mut evaluator = Evaluator::new(Rc::clone(&bag));
+        evaluator.evaluate(&ast);
+
+        (evaluator.last_value, take_diagnostics(&bag))
+    }
+
+    /// Evaluate `input` on a stack the size `main` evaluates on, returning the
+    /// message of every diagnostic.
+    fn evaluate_deep(input: String) -> Vec<String> {
+        with_stack(stack_size(DEFAULT_MAX_CALL_DEPTH), move || {
+            let (_, diagnostics) = evaluate(&input);
+            messages(&diagnostics)
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn and_skips_right_operand_when_left_is_false() {
+        let (value, diagnostics) = evaluate("false && (1 / 0 == 1)");
+
+        assert_eq!(value, Some(Value::Bool(false)));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn or_skips_right_operand_when_left_is_true() {
+        let (value, diagnostics) = evaluate("true || 1 + true");
+
+        assert_eq!(value, Some(Value::Bool(true)));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn logical_operators_evaluate_right_operand_when_needed() {
+        assert_eq!(evaluate("true && false").0, Some(Value::Bool(false)));
+        assert_eq!(evaluate("false || true").0, Some(Value::Bool(true)));
+
+        let (value, diagnostics) = evaluate("true && (1 / 0 == 1)");
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["attempt to divide by zero"]);
+    }
+
+    #[test]
+    fn logical_operators_reject_non_bool_left_operand() {
+        let (value, diagnostics) = evaluate("1 && true");
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["cannot apply `&&` to `int`"]);
+    }
+
+    #[test]
+    fn logical_operators_reject_non_bool_right_operand() {
+        let (value, diagnostics) = evaluate("false || 1");
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["cannot apply `||` to `int`"]);
+    }
+
+    #[test]
+    fn overflow_is_reported_at_operator() {
+        let (value, diagnostics) = evaluate("let a = 9223372036854775807\na + 1");
+
+        assert_eq!(value, None);
+        assert_eq!(
+            messages(&diagnostics),
+            ["attempt to apply `+` with overflow"]
+        );
+        assert_eq!(diagnostics[0].span, TextSpan::new(30, 31, "+".to_string()));
+    }
+
+    #[test]
+    fn division_by_zero_is_reported_at_operator() {
+        let (value, diagnostics) = evaluate("10 / (5 - 5)");
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["attempt to divide by zero"]);
+        assert_eq!(diagnostics[0].span, TextSpan::new(3, 4, "/".to_string()));
+
+        let (_, diagnostics) = evaluate("10 % 0");
+
+        assert_eq!(
+            messages(&diagnostics),
+            ["attempt to calculate the remainder with a divisor of zero"]
+        );
+        assert_eq!(diagnostics[0].span, TextSpan::new(3, 4, "%".to_string()));
+    }
+
+    #[test]
+    fn negating_minimum_overflows() {
+        let (value, diagnostics) = evaluate("let a = -9223372036854775807 - 1\n-a");
+
+        assert_eq!(value, None);
+        assert_eq!(
+            messages(&diagnostics),
+            ["attempt to apply `-` with overflow"]
+        );
+        assert_eq!(diagnostics[0].span, TextSpan::new(33, 34, "-".to_string()));
+    }
+
+    #[test]
+    fn mixed_int_and_float_operands_promote_to_float() {
+        assert_eq!(evaluate("1 + 2.5").0, Some(Value::Float(3.5)));
+        assert_eq!(evaluate("2.5 * 2").0, Some(Value::Float(5.0)));
+        assert_eq!(evaluate("3 / 2.0").0, Some(Value::Float(1.5)));
+        assert_eq!(evaluate("1 < 1.5").0, Some(Value::Bool(true)));
+        assert_eq!(evaluate("2 == 2.0").0, Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn int_operands_stay_int() {
+        assert_eq!(evaluate("3 / 2").0, Some(Value::Int(1)));
+    }
+
+    #[test]
+    fn float_division_by_zero_follows_ieee_754() {
+        let (value, diagnostics) = evaluate("1.0 / 0");
+
+        assert_eq!(value, Some(Value::Float(f64::INFINITY)));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn float_modulo_is_rejected() {
+        let (value, diagnostics) = evaluate("5.5 % 2");
+
+        assert_eq!(value, None);
+        assert_eq!(
+            messages(&diagnostics),
+            ["cannot apply `%` to `float` and `int`"]
+        );
+    }
+
+    #[test]
+    fn strings_concatenate_and_compare() {
+        assert_eq!(
+            evaluate("\"foo\" + \"bar\"").0,
+            Some(Value::String("foobar".to_string()))
+        );
+        assert_eq!(evaluate("\"a\" == \"a\"").0, Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn shadowing_in_a_block_ends_with_the_block() {
+        assert_eq!(
+            evaluate("let a = 1\n{ let a = 2 }\na").0,
+            Some(Value::Int(1))
+        );
+        assert_eq!(
+            evaluate("let a = 1\nlet b = { let a = 2\na }\na * 10 + b").0,
+            Some(Value::Int(12))
+        );
+    }
+
+    #[test]
+    fn assignment_in_a_block_changes_the_outer_variable() {
+        assert_eq!(evaluate("let a = 1\n{ a = 2 }\na").0, Some(Value::Int(2)));
+    }
+
+    #[test]
+    fn if_evaluates_the_branch_chosen_by_its_condition() {
+        let input = "let a = 2\nif a == 1 { 10 } else if a == 2 { 20 } else { 30 }";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(20)));
+        assert_eq!(evaluate("if false { 1 }").0, Some(Value::Unit));
+    }
+
+    #[test]
+    fn non_bool_condition_is_reported() {
+        let (value, diagnostics) = evaluate("if 1 { 2 } else { 3 }");
+
+        assert_eq!(value, None);
+        assert_eq!(
+            messages(&diagnostics),
+            ["expected `bool` condition, found `int`"]
+        );
+    }
+
+    #[test]
+    fn while_loops_obey_break_and_continue() {
+        let input = "
+            let i = 0
+            let sum = 0
+            while i < 10 {
+                i += 1
+                if i % 2 == 0 { continue }
+                if i > 7 { break }
+                sum += i
+            }
+            sum * 100 + i
+        ";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(1609)));
+    }
+
+    #[test]
+    fn for_loops_iterate_over_ranges() {
+        let sum = |range| evaluate(&format!("let s = 0\nfor i in {range} {{ s += i }}\ns")).0;
+
+        assert_eq!(sum("0..5"), Some(Value::Int(10)));
+        assert_eq!(sum("0..=5"), Some(Value::Int(15)));
+        assert_eq!(sum("5..0"), Some(Value::Int(0)));
+    }
+
+    #[test]
+    fn inclusive_range_ending_at_max_does_not_overflow() {
+        let input = "
+            let count = 0
+            for i in 9223372036854775805..=9223372036854775807 { count += 1 }
+            count
+        ";
+        let (value, diagnostics) = evaluate(input);
+
+        assert_eq!(value, Some(Value::Int(3)));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn iterating_over_a_non_range_is_reported() {
+        let (_, diagnostics) = evaluate("for i in 3 { i }");
+
+        assert_eq!(
+            messages(&diagnostics),
+            ["cannot iterate over `int`, expected a range"]
+        );
+    }
+
+    #[test]
+    fn top_level_functions_can_call_each_other_in_any_order() {
+        let input = "
+            let x = even(10)
+            fn even(n) { if n == 0 { true } else { odd(n - 1) } }
+            fn odd(n) { if n == 0 { false } else { even(n - 1) } }
+            x
+        ";
+
+        let (value, diagnostics) = evaluate(input);
+
+        assert_eq!(value, Some(Value::Bool(true)));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn local_functions_capture_earlier_siblings_and_variables() {
+        let input = "
+            fn outer() {
+                let x = 1
+                fn a() { x }
+                fn b() { a() + 1 }
+                b()
+            }
+            outer()
+        ";
+
+        let (value, diagnostics) = evaluate(input);
+
+        assert_eq!(value, Some(Value::Int(2)));
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            evaluate("fn outer() { fn a() {1}; fn b() { a() }; b() }\nouter()").0,
+            Some(Value::Int(1))
+        );
+    }
+
+    #[test]
+    fn runaway_recursion_is_reported_as_a_stack_overflow() {
+        assert_eq!(
+            evaluate_deep("fn f() { f() }\nf()".to_string()),
+            ["stack overflow, calls are nested more than 500 deep"]
+        );
+    }
+
+    #[test]
+    fn deep_code_in_each_call_is_reported_as_a_stack_overflow() {
+        // Fewer calls than the limit, but each one nests 90 levels deeper
+        let input = format!(
+            "fn f(n) {{ {}if n == 0 {{ 0 }} else {{ f(n - 1) }}{} }}\nf(499)",
+            "(".repeat(90),
+            ")".repeat(90)
+        );
+
+        assert_eq!(
+            evaluate_deep(input),
+            ["stack overflow, calls and the code in them are nested more than 32000 levels deep"]
+        );
+    }
+
+    #[test]
+    fn calls_with_the_wrong_number_of_arguments_are_reported() {
+        let (_, diagnostics) = evaluate("fn f(a, b) { a }\nf(1)");
+
+        assert_eq!(messages(&diagnostics), ["expected 2 arguments, found 1"]);
+        assert_eq!(
+            diagnostics[0].span,
+            TextSpan::new(17, 21, "f(1)".to_string())
+        );
+
+        let (_, diagnostics) = evaluate("let f = |x| x\nf(1, 2)");
+
+        assert_eq!(messages(&diagnostics), ["expected 1 argument, found 2"]);
+    }
+
+    #[test]
+    fn return_leaves_the_function_from_inside_loops_and_blocks() {
+        let input = "
+            fn first_square_over(limit) {
+                for i in 0..100 {
+                    { if i * i > limit { return i } }
+                }
+                -1
+            }
+            first_square_over(50) * 1000 + first_square_over(100000)
+        ";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(7999)));
+    }
+
+    #[test]
+    fn nothing_after_return_runs() {
+        let input = "
+            let x = 0
+            fn f() { x = 1; return 2; x = 3 }
+            f() * 10 + x
+        ";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(21)));
+    }
+
+    #[test]
+    fn reading_a_variable_before_its_let_ran_is_reported() {
+        let input = "let x = f()\nlet y = 1\nfn f() { y }\nx + 1\n42";
+        let (value, diagnostics) = evaluate(input);
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["`y` used before its `let` ran"]);
+        assert_eq!(diagnostics[0].span, TextSpan::new(31, 32, "y".to_string()));
+    }
+
+    #[test]
+    fn closures_capture_locals_by_value() {
+        let input = "{ let x = 1; let f = || x; x = 2; f() }";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(1)));
+    }
+
+    #[test]
+    fn each_call_starts_from_the_captured_copy() {
+        let input = "
+            {
+                let count = 0
+                let next = || { count += 1; count }
+                next() * 100 + next() * 10 + count
+            }
+        ";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(110)));
+    }
+
+    #[test]
+    fn globals_are_shared_with_closures() {
+        let input = "let x = 1\nlet f = || x\nx = 2\nf()";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(2)));
+    }
+
+    #[test]
+    fn closures_and_anonymous_functions_can_be_called() {
+        let input = "
+            let inc = |x| x + 1
+            let double = fn(x) { x * 2 }
+            double(inc(3)) * 10 + (|x| x)(1)
+        ";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(81)));
+    }
+
+    #[test]
+    fn closures_outlive_the_call_that_made_them() {
+        let input = "
+            fn adder(n) { |x| x + n }
+            let add2 = adder(2)
+            let add5 = adder(5)
+            add2(1) * 10 + add5(1)
+        ";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(36)));
+    }
+
+    #[test]
+    fn closure_bound_to_a_global_can_recurse() {
+        let input = "let f = |n| if n == 0 { 0 } else { n + f(n - 1) }\nf(4)";
+        let (value, diagnostics) = evaluate(input);
+
+        assert_eq!(value, Some(Value::Int(10)));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn operators_follow_precedence_and_associativity() {
+        for (input, expected) in [
+            ("10 - 2 - 3", 5),
+            ("7 - (3 + 7) * 8 / 2", -33),
+            ("12 % 5 * 5", 10),
+            ("10 - 2 + 3", 11),
+            ("100 / 10 / 5", 2),
+            ("10 - (2 - 3)", 11),
+            ("1 + 2 * 3 - 4", 3),
+        ] {
+            assert_eq!(evaluate(input).0, Some(Value::Int(expected)), "{input}");
+        }
+    }
+
+    #[test]
+    fn bitwise_operators_work_on_integers() {
+        for (input, expected) in [
+            ("6 & 3", 2),
+            ("6 | 3", 7),
+            ("6 ^ 3", 5),
+            ("~5", -6),
+            ("1 << 3", 8),
+            ("-16 >> 2", -4),
+            ("1 << 63", i64::MIN),
+            ("1 | 2 ^ 3 & 4", 3),
+        ] {
+            assert_eq!(evaluate(input).0, Some(Value::Int(expected)), "{input}");
+        }
+    }
+
+    #[test]
+    fn shift_amounts_outside_the_range_are_reported() {
+        for (input, amount) in [("1 << 64", "64"), ("1 << -1", "-1"), ("8 >> 64", "64")] {
+            let (value, diagnostics) = evaluate(input);
+
+            assert_eq!(value, None, "{input}");
+            assert_eq!(
+                messages(&diagnostics),
+                [format!(
+                    "shift amount `{amount}` is outside the range 0..64"
+                )],
+                "{input}"
+            );
+            assert_eq!(diagnostics[0].span.start, 2, "{input}");
+        }
+    }
+
+    #[test]
+    fn unary_operators_apply_to_their_operand() {
+        for (input, expected) in [
+            ("-5", Value::Int(-5)),
+            ("+5", Value::Int(5)),
+            ("- -5", Value::Int(5)),
+            ("-2 * 3", Value::Int(-6)),
+            ("-(2 + 3)", Value::Int(-5)),
+            ("-1.5", Value::Float(-1.5)),
+            ("+1.5", Value::Float(1.5)),
+            ("!true", Value::Bool(false)),
+            ("!!true", Value::Bool(true)),
+        ] {
+            assert_eq!(evaluate(input).0, Some(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn unary_operator_errors_are_reported() {
+        for (input, message) in [
+            ("-true", "cannot apply `-` to `bool`"),
+            ("!1", "cannot apply `!` to `int`"),
+            ("+\"a\"", "cannot apply `+` to `string`"),
+            (
+                "-(-9223372036854775807 - 1)",
+                "attempt to apply `-` with overflow",
+            ),
+        ] {
+            let (value, diagnostics) = evaluate(input);
+
+            assert_eq!(value, None, "{input}");
+            assert_eq!(messages(&diagnostics), [message], "{input}");
+            assert_eq!(diagnostics[0].span.start, 0, "{input}");
+        }
+    }
+
+    #[test]
+    fn comparisons_give_bools() {
+        for (input, expected) in [
+            ("1 < 2", true),
+            ("2 <= 2", true),
+            ("3 > 4", false),
+            ("3 >= 4", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("true == false", false),
+            ("true != false", true),
+            ("1 + 1 == 2", true),
+            ("1 < 2 == true", true),
+        ] {
+            assert_eq!(evaluate(input).0, Some(Value::Bool(expected)), "{input}");
+        }
     }
 
-    fn visit_error(&mut self, _span: &TextSpan) {
-        println!("Cannot evaluate error expression");
+    #[test]
+    fn comparing_mismatched_types_is_reported() {
+        for (input, message) in [
+            ("1 == true", "cannot apply `==` to `int` and `bool`"),
+            ("false < 1", "cannot apply `<` to `bool` and `int`"),
+            ("true >= false", "cannot apply `>=` to `bool` and `bool`"),
+        ] {
+            let (value, diagnostics) = evaluate(input);
+
+            assert_eq!(value, None, "{input}");
+            assert_eq!(messages(&diagnostics), [message], "{input}");
+        }
+    }
+
+    #[test]
+    fn nothing_is_evaluated_after_the_resolver_reports_an_error() {
+        let (value, diagnostics) = evaluate("fn f() { x }\nf()");
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["use of undeclared variable `x`"]);
+    }
+
+    #[test]
+    fn compound_assignment_applies_the_operator() {
+        let input = "let a = 10\na += 5\na -= 3\na *= 2\na /= 4\na %= 4\na";
+
+        assert_eq!(evaluate(input).0, Some(Value::Int(2)));
+        assert_eq!(
+            evaluate("let s = \"a\"\ns += \"b\"\ns").0,
+            Some(Value::String("ab".to_string()))
+        );
+    }
+
+    #[test]
+    fn compound_assignment_errors_are_reported() {
+        let (value, diagnostics) = evaluate("let a = 1\na /= 0\na");
+
+        assert_eq!(value, None);
+        assert_eq!(messages(&diagnostics), ["attempt to divide by zero"]);
+        assert_eq!(diagnostics[0].span, TextSpan::new(12, 14, "/=".to_string()));
+    }
+
+    #[test]
+    fn assigning_before_the_let_ran_is_reported() {
+        let (value, diagnostics) = evaluate("let x = f()\nlet y = 1\nfn f() { y = 2 }");
+
+        assert_eq!(value, None);
+        assert_eq!(
+            messages(&diagnostics),
+            ["`y` assigned before its `let` ran"]
+        );
+        assert_eq!(diagnostics[0].span, TextSpan::new(31, 32, "y".to_string()));
+    }
+
+    #[test]
+    fn demo_program_evaluates_to_30() {
+        let (value, diagnostics) = evaluate(crate::DEMO);
+
+        assert_eq!(value, Some(Value::Int(30)));
+        assert!(diagnostics.is_empty());
     }
 }
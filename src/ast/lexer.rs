@@ -1,43 +1,172 @@
 #![cfg_attr(debug_assertions, allow(dead_code))]
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+use crate::diagnostics::BagCell;
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum TokenKind {
     Number(i64),
+    Float(f64),
+    String(String),
     Plus,
     Minus,
     Asterisk,
     Slash,
     LeftParen,
     RightParen,
+    LeftBrace,
+    RightBrace,
     Bad,
     Mod,
     Whitespace,
+    Newline,
+    Semicolon,
+    LineComment,
+    BlockComment,
     Let,
+    If,
+    Else,
+    While,
+    Break,
+    Continue,
+    For,
+    In,
+    Fn,
+    Return,
+    True,
+    False,
     Identifier(String),
     Equals,
+    PlusEquals,
+    MinusEquals,
+    AsteriskEquals,
+    SlashEquals,
+    ModEquals,
+    EqualsEquals,
+    BangEquals,
+    LessThan,
+    LessThanEquals,
+    GreaterThan,
+    GreaterThanEquals,
+    AmpersandAmpersand,
+    PipePipe,
+    Bang,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LessThanLessThan,
+    GreaterThanGreaterThan,
+    DotDot,
+    DotDotEquals,
+    Comma,
     Eof,
 }
 
+impl TokenKind {
+    /// Whether a line ending in this token carries on to the next line rather
+    /// than ending the statement.
+    pub const fn continues_line(&self) -> bool {
+        matches!(
+            self,
+            Self::Plus
+                | Self::Minus
+                | Self::Asterisk
+                | Self::Slash
+                | Self::Mod
+                | Self::LeftParen
+                | Self::LeftBrace
+                | Self::Equals
+                | Self::PlusEquals
+                | Self::MinusEquals
+                | Self::AsteriskEquals
+                | Self::SlashEquals
+                | Self::ModEquals
+                | Self::EqualsEquals
+                | Self::BangEquals
+                | Self::LessThan
+                | Self::LessThanEquals
+                | Self::GreaterThan
+                | Self::GreaterThanEquals
+                | Self::AmpersandAmpersand
+                | Self::PipePipe
+                | Self::Bang
+                | Self::Ampersand
+                | Self::Pipe
+                | Self::Caret
+                | Self::Tilde
+                | Self::LessThanLessThan
+                | Self::GreaterThanGreaterThan
+                | Self::DotDot
+                | Self::DotDotEquals
+                | Self::Comma
+                | Self::Newline
+                | Self::Semicolon
+        )
+    }
+}
+
 impl std::fmt::Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let string = match self {
             Self::Number(_) => "Number",
+            Self::Float(_) => "Float",
+            Self::String(_) => "String",
             Self::Plus => "+",
             Self::Minus => "-",
             Self::Asterisk => "*",
             Self::Slash => "/",
             Self::LeftParen => "(",
             Self::RightParen => ")",
+            Self::LeftBrace => "{",
+            Self::RightBrace => "}",
             Self::Bad => "Bad",
             Self::Mod => "%",
             Self::Whitespace => "Whitespace",
+            Self::Newline => "newline",
+            Self::Semicolon => ";",
+            Self::LineComment => "LineComment",
+            Self::BlockComment => "BlockComment",
             Self::Let => "Let",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::While => "while",
+            Self::Break => "break",
+            Self::Continue => "continue",
+            Self::For => "for",
+            Self::In => "in",
+            Self::Fn => "fn",
+            Self::Return => "return",
+            Self::True => "true",
+            Self::False => "false",
             Self::Identifier(identifier) => identifier,
             Self::Equals => "=",
+            Self::PlusEquals => "+=",
+            Self::MinusEquals => "-=",
+            Self::AsteriskEquals => "*=",
+            Self::SlashEquals => "/=",
+            Self::ModEquals => "%=",
+            Self::EqualsEquals => "==",
+            Self::BangEquals => "!=",
+            Self::LessThan => "<",
+            Self::LessThanEquals => "<=",
+            Self::GreaterThan => ">",
+            Self::GreaterThanEquals => ">=",
+            Self::AmpersandAmpersand => "&&",
+            Self::PipePipe => "||",
+            Self::Bang => "!",
+            Self::Ampersand => "&",
+            Self::Pipe => "|",
+            Self::Caret => "^",
+            Self::Tilde => "~",
+            Self::LessThanLessThan => "<<",
+            Self::GreaterThanGreaterThan => ">>",
+            Self::DotDot => "..",
+            Self::DotDotEquals => "..=",
+            Self::Comma => ",",
             Self::Eof => "EOF",
         };
 
-        write!(f, "{}", string.to_string())
+        write!(f, "{string}")
     }
 }
 
@@ -62,33 +191,160 @@ impl TextSpan {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: TextSpan,
+    /// Comments on the lines before this token.
+    pub leading_trivia: Vec<Self>,
+    /// Comments after this token on the same line.
+    pub trailing_trivia: Vec<Self>,
 }
 
 impl Token {
     pub const fn new(kind: TokenKind, span: TextSpan) -> Self {
-        Self { kind, span }
+        Self {
+            kind,
+            span,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+        }
+    }
+
+    pub const fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
     }
 }
 
+/// Drop whitespace and attach comments to the surrounding tokens. A comment
+/// after a token on the same line trails that token, any other comment leads
+/// the next token, so comments at the end of the file lead `Eof`.
+///
+/// Newlines end statements, so they are only kept where a statement could end:
+/// a line ending in an operator, `(` or `{`, or inside parentheses, continues
+/// onto the next line, and blank lines collapse into a single newline. Braces
+/// hold statements again, so newlines in a block inside parentheses count.
+pub fn attach_trivia(tokens: impl Iterator<Item = Token>) -> Vec<Token> {
+    let mut result: Vec<Token> = Vec::new();
+    let mut leading_trivia = Vec::new();
+    let mut is_same_line = false;
+    // Whether each open delimiter is a `(` rather than a `{`
+    let mut open_delimiters = Vec::new();
+
+    for mut token in tokens {
+        match token.kind {
+            TokenKind::LeftParen => open_delimiters.push(true),
+            TokenKind::LeftBrace => open_delimiters.push(false),
+            TokenKind::RightParen | TokenKind::RightBrace => {
+                open_delimiters.pop();
+            }
+            TokenKind::Newline => {
+                is_same_line = false;
+
+                let is_in_parens = open_delimiters.last() == Some(&true);
+                let continues_line = result
+                    .last()
+                    .is_none_or(|previous| previous.kind.continues_line());
+
+                if is_in_parens || continues_line {
+                    continue;
+                }
+            }
+            _ => {}
+        }
+
+        if !token.is_trivia() {
+            token.leading_trivia = std::mem::take(&mut leading_trivia);
+            is_same_line = token.kind != TokenKind::Newline;
+            result.push(token);
+            continue;
+        }
+
+        // Only a block comment can span lines here, newlines are tokens of their own
+        let ends_line = token.span.literal.contains('\n');
+
+        if token.kind != TokenKind::Whitespace {
+            match result.last_mut() {
+                Some(previous) if is_same_line => previous.trailing_trivia.push(token),
+                _ => leading_trivia.push(token),
+            }
+        }
+
+        if ends_line {
+            is_same_line = false;
+        }
+    }
+
+    result
+}
+
+/// Words that can never be used as identifiers.
+const KEYWORDS: [(&str, TokenKind); 12] = [
+    ("let", TokenKind::Let),
+    ("if", TokenKind::If),
+    ("else", TokenKind::Else),
+    ("while", TokenKind::While),
+    ("break", TokenKind::Break),
+    ("continue", TokenKind::Continue),
+    ("for", TokenKind::For),
+    ("in", TokenKind::In),
+    ("fn", TokenKind::Fn),
+    ("return", TokenKind::Return),
+    ("true", TokenKind::True),
+    ("false", TokenKind::False),
+];
+
+fn keyword(identifier: &str) -> Option<TokenKind> {
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == identifier)
+        .map(|(_, kind)| kind.clone())
+}
+
+/// Identifiers follow UAX #31, with `_` also allowed to start one.
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+/// The result of lexing a run of digits.
+struct Digits {
+    /// `None` if the digits overflow an `i64`.
+    value: Option<i64>,
+    is_empty: bool,
+    is_valid: bool,
+}
+
+/// Positions and spans are byte offsets into `input`, always on char boundaries.
 pub struct Lexer<'a> {
     input: &'a str,
     current_pos: usize,
+    is_finished: bool,
+    bag: BagCell,
 }
 
 impl Iterator for Lexer<'_> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished {
+            return None;
+        }
+
+        // Eof is an empty span at the very end, so diagnostics about a missing
+        // token point past the last char rather than at the start of the file
         if self.current_pos == self.input.len() {
-            self.current_pos += 1;
+            self.is_finished = true;
 
             return Some(Token::new(
                 TokenKind::Eof,
-                TextSpan::new(0, 0, '\0'.to_string()),
+                TextSpan::new(self.current_pos, self.current_pos, String::new()),
             ));
         }
 
@@ -98,9 +354,13 @@ impl Iterator for Lexer<'_> {
             let start = self.current_pos;
 
             let kind = match char {
-                _ if char.is_ascii_digit() => {
-                    let number = self.consume_number();
-                    TokenKind::Number(number)
+                _ if char.is_ascii_digit() => self.consume_number(),
+
+                '"' => self.consume_string(),
+
+                '\n' => {
+                    self.consume();
+                    TokenKind::Newline
                 }
 
                 _ if char.is_whitespace() => {
@@ -108,13 +368,10 @@ impl Iterator for Lexer<'_> {
                     TokenKind::Whitespace
                 }
 
-                _ if char.is_alphabetic() => {
+                _ if is_identifier_start(char) => {
                     let identifier = self.consume_identifier();
-
-                    match identifier.as_str() {
-                        "let" => TokenKind::Let,
-                        _ => TokenKind::Identifier(identifier),
-                    }
+                    keyword(identifier)
+                        .unwrap_or_else(|| TokenKind::Identifier(identifier.to_string()))
                 }
 
                 _ => self.consume_punctuation(),
@@ -130,70 +387,643 @@ impl Iterator for Lexer<'_> {
 }
 
 impl<'a> Lexer<'a> {
-    pub const fn new(input: &'a str) -> Self {
+    pub const fn new(input: &'a str, bag: BagCell) -> Self {
         Self {
             input,
             current_pos: 0,
+            is_finished: false,
+            bag,
         }
     }
 
     fn consume(&mut self) -> Option<char> {
-        if self.current_pos >= self.input.len() {
-            return None;
+        let char = self.current_char()?;
+        self.current_pos += char.len_utf8();
+        Some(char)
+    }
+
+    /// Consume a number literal. Integers may have a `0x`, `0o` or `0b` radix
+    /// prefix, decimal literals may have a fraction and exponent making them a
+    /// float, and both may use `_` separators between digits. Produces
+    /// `TokenKind::Bad` and reports a diagnostic if the literal is malformed or
+    /// does not fit in its type.
+    fn consume_number(&mut self) -> TokenKind {
+        let start = self.current_pos;
+        let radix = self.consume_radix_prefix();
+        let digits = self.consume_digits(radix);
+
+        if radix == 10 && (self.is_at_fraction() || self.is_at_exponent()) {
+            return self.consume_float(start, digits.is_valid);
         }
 
-        let char = self.current_char();
-        self.current_pos += 1;
-        char
+        if !digits.is_valid {
+            return TokenKind::Bad;
+        }
+
+        let span = self.span_from(start);
+
+        if digits.is_empty {
+            self.bag.borrow_mut().report_missing_digits(span);
+            return TokenKind::Bad;
+        }
+
+        digits.value.map_or_else(
+            || {
+                self.bag.borrow_mut().report_integer_literal_too_large(span);
+                TokenKind::Bad
+            },
+            TokenKind::Number,
+        )
+    }
+
+    /// Consume the fraction and exponent of a float whose integer part ends at the current char.
+    fn consume_float(&mut self, start: usize, mut is_valid: bool) -> TokenKind {
+        if self.is_at_fraction() {
+            self.consume();
+            is_valid &= self.consume_digits(10).is_valid;
+        }
+
+        if self.is_at_exponent() {
+            self.consume();
+            self.consume_if('+');
+            self.consume_if('-');
+            is_valid &= self.consume_digits(10).is_valid;
+        }
+
+        if !is_valid {
+            return TokenKind::Bad;
+        }
+
+        let span = self.span_from(start);
+        let number: f64 = span
+            .literal
+            .replace('_', "")
+            .parse()
+            .expect("Float literals are validated while lexing");
+
+        if number.is_infinite() {
+            self.bag.borrow_mut().report_float_literal_too_large(span);
+            return TokenKind::Bad;
+        }
+
+        TokenKind::Float(number)
     }
 
-    fn consume_number(&mut self) -> i64 {
-        let mut number: i64 = 0;
+    /// Consume a run of digits and `_` separators in the given radix.
+    ///
+    /// Letters are consumed too so `0b102` or `12ab` is reported as a single
+    /// bad literal, except for a decimal exponent.
+    fn consume_digits(&mut self, radix: u32) -> Digits {
+        let mut digits = Digits {
+            value: Some(0),
+            is_empty: true,
+            is_valid: true,
+        };
 
         while let Some(c) = self.current_char() {
-            if c.is_ascii_digit() {
-                self.consume();
-                number = number * 10 + i64::from(c.to_digit(10).expect("Bad digit"));
-            } else {
+            if c != '_' && !c.is_ascii_alphanumeric() || radix == 10 && self.is_at_exponent() {
                 break;
             }
+
+            let position = self.current_pos;
+            self.consume();
+
+            if c == '_' {
+                continue;
+            }
+
+            let Some(digit) = c.to_digit(radix) else {
+                if digits.is_valid {
+                    let span = TextSpan::new(position, position + c.len_utf8(), c.to_string());
+                    self.bag.borrow_mut().report_invalid_digit(c, radix, span);
+                }
+
+                digits.is_valid = false;
+                continue;
+            };
+
+            // Keep consuming digits after an overflow so the whole literal is one token
+            digits.is_empty = false;
+            digits.value = digits
+                .value
+                .and_then(|value| value.checked_mul(i64::from(radix)))
+                .and_then(|value| value.checked_add(i64::from(digit)));
+        }
+
+        digits
+    }
+
+    /// A `.` only starts a fraction when a digit follows, so `1..2` stays a range.
+    fn is_at_fraction(&self) -> bool {
+        self.current_char() == Some('.') && self.peek_char(1).is_some_and(|c| c.is_ascii_digit())
+    }
+
+    fn is_at_exponent(&self) -> bool {
+        if !matches!(self.current_char(), Some('e' | 'E')) {
+            return false;
+        }
+
+        match self.peek_char(1) {
+            Some('+' | '-') => self.peek_char(2).is_some_and(|c| c.is_ascii_digit()),
+            next => next.is_some_and(|c| c.is_ascii_digit()),
+        }
+    }
+
+    /// Consume a string literal, decoding its escape sequences. Produces
+    /// `TokenKind::Bad` and reports a diagnostic if the literal is unterminated
+    /// or contains an invalid escape.
+    fn consume_string(&mut self) -> TokenKind {
+        let start = self.current_pos;
+        self.consume();
+
+        let mut value = String::new();
+        let mut is_valid = true;
+
+        loop {
+            let position = self.current_pos;
+
+            match self.consume() {
+                None => {
+                    let span = TextSpan::new(start, start + 1, '"'.to_string());
+                    self.bag.borrow_mut().report_unterminated_string(span);
+                    return TokenKind::Bad;
+                }
+                Some('"') => break,
+                Some('\\') => {
+                    if let Some(c) = self.consume_escape() {
+                        value.push(c);
+                    } else {
+                        let span = self.span_from(position);
+                        self.bag.borrow_mut().report_invalid_escape(span);
+                        is_valid = false;
+                    }
+                }
+                Some(c) => value.push(c),
+            }
+        }
+
+        if is_valid {
+            TokenKind::String(value)
+        } else {
+            TokenKind::Bad
+        }
+    }
+
+    /// Consume the escape sequence following a `\`, returning `None` if it is invalid.
+    fn consume_escape(&mut self) -> Option<char> {
+        match self.consume()? {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            'u' => self.consume_unicode_escape(),
+            _ => None,
+        }
+    }
+
+    /// Consume the rest of a `//` comment, leaving the newline.
+    fn consume_line_comment(&mut self) -> TokenKind {
+        while self.current_char().is_some_and(|c| c != '\n') {
+            self.consume();
+        }
+
+        TokenKind::LineComment
+    }
+
+    /// Consume the rest of a `/*` comment. Block comments nest, so every `/*`
+    /// inside needs its own `*/`.
+    fn consume_block_comment(&mut self) -> TokenKind {
+        let start = self.current_pos - 2;
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.consume() {
+                None => {
+                    let span = TextSpan::new(start, start + 2, "/*".to_string());
+                    self.bag
+                        .borrow_mut()
+                        .report_unterminated_block_comment(span);
+                    break;
+                }
+                Some('/') if self.consume_if('*') => depth += 1,
+                Some('*') if self.consume_if('/') => depth -= 1,
+                Some(_) => {}
+            }
+        }
+
+        TokenKind::BlockComment
+    }
+
+    /// Consume the `{...}` of a `\u{...}` escape, holding 1 to 6 hex digits.
+    fn consume_unicode_escape(&mut self) -> Option<char> {
+        if !self.consume_if('{') {
+            return None;
+        }
+
+        let mut code: u32 = 0;
+        let mut digits = 0;
+
+        // Consume every digit so an overlong escape is reported as a whole
+        while let Some(digit) = self.current_char().and_then(|c| c.to_digit(16)) {
+            self.consume();
+            code = code.saturating_mul(16).saturating_add(digit);
+            digits += 1;
+        }
+
+        if !self.consume_if('}') || !(1..=6).contains(&digits) {
+            return None;
+        }
+
+        char::from_u32(code)
+    }
+
+    /// Consume a `0x`, `0o` or `0b` prefix if there is one, returning the radix of the literal.
+    fn consume_radix_prefix(&mut self) -> u32 {
+        if self.current_char() != Some('0') {
+            return 10;
         }
 
-        number
+        let radix = match self.peek_char(1) {
+            Some('x' | 'X') => 16,
+            Some('o' | 'O') => 8,
+            Some('b' | 'B') => 2,
+            _ => return 10,
+        };
+
+        self.consume();
+        self.consume();
+
+        radix
     }
 
     fn consume_punctuation(&mut self) -> TokenKind {
         let char = self.consume().expect("Failed to get next char.");
 
         match char {
+            '+' if self.consume_if('=') => TokenKind::PlusEquals,
+            '-' if self.consume_if('=') => TokenKind::MinusEquals,
+            '*' if self.consume_if('=') => TokenKind::AsteriskEquals,
+            '/' if self.consume_if('/') => self.consume_line_comment(),
+            '/' if self.consume_if('*') => self.consume_block_comment(),
+            '/' if self.consume_if('=') => TokenKind::SlashEquals,
+            '%' if self.consume_if('=') => TokenKind::ModEquals,
+            '=' if self.consume_if('=') => TokenKind::EqualsEquals,
+            '!' if self.consume_if('=') => TokenKind::BangEquals,
+            '<' if self.consume_if('=') => TokenKind::LessThanEquals,
+            '>' if self.consume_if('=') => TokenKind::GreaterThanEquals,
+            '&' if self.consume_if('&') => TokenKind::AmpersandAmpersand,
+            '|' if self.consume_if('|') => TokenKind::PipePipe,
+            '<' if self.consume_if('<') => TokenKind::LessThanLessThan,
+            '>' if self.consume_if('>') => TokenKind::GreaterThanGreaterThan,
+            '.' if self.consume_if('.') => {
+                if self.consume_if('=') {
+                    TokenKind::DotDotEquals
+                } else {
+                    TokenKind::DotDot
+                }
+            }
             '+' => TokenKind::Plus,
             '-' => TokenKind::Minus,
             '*' => TokenKind::Asterisk,
             '/' => TokenKind::Slash,
             '(' => TokenKind::LeftParen,
             ')' => TokenKind::RightParen,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
             '%' => TokenKind::Mod,
             '=' => TokenKind::Equals,
-            _ => TokenKind::Bad,
+            '<' => TokenKind::LessThan,
+            '>' => TokenKind::GreaterThan,
+            '!' => TokenKind::Bang,
+            '&' => TokenKind::Ampersand,
+            '|' => TokenKind::Pipe,
+            '^' => TokenKind::Caret,
+            '~' => TokenKind::Tilde,
+            ';' => TokenKind::Semicolon,
+            ',' => TokenKind::Comma,
+            _ => {
+                let start = self.current_pos - char.len_utf8();
+                let span = TextSpan::new(start, self.current_pos, char.to_string());
+                self.bag.borrow_mut().report_bad_character(char, span);
+                TokenKind::Bad
+            }
+        }
+    }
+
+    /// Consume the next char only if it matches `expected`.
+    fn consume_if(&mut self, expected: char) -> bool {
+        if self.current_char() == Some(expected) {
+            self.consume();
+            return true;
         }
+
+        false
     }
 
-    fn consume_identifier(&mut self) -> String {
-        let mut identifier = String::new();
+    fn consume_identifier(&mut self) -> &'a str {
+        let start = self.current_pos;
 
-        while let Some(c) = self.current_char() {
-            if c.is_alphabetic() {
-                self.consume();
-                identifier.push(c);
-            } else {
-                break;
-            }
+        while self.current_char().is_some_and(is_identifier_continue) {
+            self.consume();
         }
 
-        identifier
+        &self.input[start..self.current_pos]
+    }
+
+    fn span_from(&self, start: usize) -> TextSpan {
+        let end = self.current_pos;
+        TextSpan::new(start, end, self.input[start..end].to_string())
     }
 
     fn current_char(&self) -> Option<char> {
-        self.input.chars().nth(self.current_pos)
+        self.input[self.current_pos..].chars().next()
+    }
+
+    /// Look `offset` chars ahead, this is linear in `offset` so keep it small.
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        self.input[self.current_pos..].chars().nth(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{rc::Rc, time::Instant};
+
+    use super::{attach_trivia, Lexer, TextSpan, Token, TokenKind, KEYWORDS};
+    use crate::ast::test_helpers::{messages, new_bag, take_diagnostics};
+    use crate::diagnostics::Diagnostic;
+
+    /// Lex `input` into every token, including whitespace and the final `Eof`,
+    /// along with the diagnostics reported while lexing.
+    fn lex(input: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+        let bag = new_bag();
+        let tokens = Lexer::new(input, Rc::clone(&bag)).collect();
+
+        (tokens, take_diagnostics(&bag))
+    }
+
+    #[test]
+    fn integer_literal_up_to_max_lexes() {
+        let (tokens, diagnostics) = lex("9223372036854775807");
+
+        assert_eq!(tokens[0].kind, TokenKind::Number(i64::MAX));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn integer_literal_too_large_is_reported() {
+        let (tokens, diagnostics) = lex("1 + 9223372036854775808");
+
+        assert_eq!(tokens[4].kind, TokenKind::Bad);
+        assert_eq!(messages(&diagnostics), ["integer literal too large"]);
+        assert_eq!(
+            diagnostics[0].span,
+            TextSpan::new(4, 23, "9223372036854775808".to_string())
+        );
+    }
+
+    #[test]
+    fn integer_literals_accept_radix_prefixes_and_separators() {
+        let (tokens, diagnostics) = lex("0xFF 0o17 0b1010 1_000_000 0x_ff_ff");
+        let numbers: Vec<_> = tokens
+            .into_iter()
+            .map(|token| token.kind)
+            .filter(|kind| matches!(kind, TokenKind::Number(_)))
+            .collect();
+
+        assert_eq!(
+            numbers,
+            [255, 15, 10, 1_000_000, 0xffff].map(TokenKind::Number)
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn invalid_digit_is_reported_at_the_digit() {
+        let (tokens, diagnostics) = lex("0b102");
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].kind, TokenKind::Bad);
+        assert_eq!(tokens[0].span.literal, "0b102");
+        assert_eq!(
+            messages(&diagnostics),
+            ["invalid digit `2` in binary literal"]
+        );
+        assert_eq!(diagnostics[0].span, TextSpan::new(4, 5, "2".to_string()));
+    }
+
+    #[test]
+    fn radix_prefix_without_digits_is_reported() {
+        let (tokens, diagnostics) = lex("0x");
+
+        assert_eq!(tokens[0].kind, TokenKind::Bad);
+        assert_eq!(messages(&diagnostics), ["expected digits after `0x`"]);
+    }
+
+    #[test]
+    fn string_escapes_are_decoded() {
+        let (tokens, diagnostics) = lex(r#""a\n\t\\\"b""#);
+
+        assert_eq!(tokens[0].kind, TokenKind::String("a\n\t\\\"b".to_string()));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn unicode_escapes_are_decoded() {
+        let (tokens, diagnostics) = lex(r#""\u{41}\u{e9}\u{1F600}""#);
+
+        assert_eq!(tokens[0].kind, TokenKind::String("Aé😀".to_string()));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn invalid_escapes_are_reported() {
+        for (input, escape) in [
+            (r#""\q""#, r"\q"),
+            (r#""\u{D800}""#, r"\u{D800}"),
+            (r#""\u{1234567}""#, r"\u{1234567}"),
+        ] {
+            let (tokens, diagnostics) = lex(input);
+
+            assert_eq!(tokens[0].kind, TokenKind::Bad, "{input}");
+            assert_eq!(diagnostics.len(), 1, "{input}");
+            assert_eq!(diagnostics[0].span.literal, escape, "{input}");
+        }
+    }
+
+    #[test]
+    fn unterminated_string_is_reported_at_opening_quote() {
+        let (tokens, diagnostics) = lex("let s = \"abc");
+
+        assert_eq!(tokens[6].kind, TokenKind::Bad);
+        assert_eq!(messages(&diagnostics), ["unterminated string literal"]);
+        assert_eq!(diagnostics[0].span, TextSpan::new(8, 9, "\"".to_string()));
+    }
+
+    /// Lex `input` and attach its trivia the way the parser sees it.
+    fn tokens_with_trivia(input: &str) -> Vec<Token> {
+        attach_trivia(lex(input).0.into_iter())
+    }
+
+    fn literals(tokens: &[Token]) -> Vec<&str> {
+        tokens
+            .iter()
+            .map(|token| token.span.literal.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let (tokens, diagnostics) = lex("/* a /* b */ c */ 1");
+
+        assert_eq!(tokens[0].kind, TokenKind::BlockComment);
+        assert_eq!(tokens[0].span.literal, "/* a /* b */ c */");
+        assert_eq!(tokens[2].kind, TokenKind::Number(1));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_reported_at_its_start() {
+        let (tokens, diagnostics) = lex("1 /* a /* b */");
+
+        assert_eq!(tokens[2].kind, TokenKind::BlockComment);
+        assert_eq!(messages(&diagnostics), ["unterminated block comment"]);
+        assert_eq!(diagnostics[0].span, TextSpan::new(2, 4, "/*".to_string()));
+    }
+
+    #[test]
+    fn comments_attach_to_surrounding_tokens() {
+        let tokens = tokens_with_trivia("// leading\nlet a = 1 /* trailing */\n// end");
+
+        assert_eq!(literals(&tokens), ["let", "a", "=", "1", "\n", ""]);
+        assert_eq!(literals(&tokens[0].leading_trivia), ["// leading"]);
+        assert_eq!(literals(&tokens[3].trailing_trivia), ["/* trailing */"]);
+        assert_eq!(tokens[5].kind, TokenKind::Eof);
+        assert_eq!(literals(&tokens[5].leading_trivia), ["// end"]);
+    }
+
+    #[test]
+    fn comment_after_multiline_block_comment_leads_next_token() {
+        let tokens = tokens_with_trivia("1 /* a\nb */ /* c */ + 2");
+
+        assert_eq!(literals(&tokens), ["1", "+", "2", ""]);
+        assert_eq!(literals(&tokens[0].trailing_trivia), ["/* a\nb */"]);
+        assert_eq!(literals(&tokens[1].leading_trivia), ["/* c */"]);
+    }
+
+    #[test]
+    fn newlines_are_kept_only_where_a_statement_can_end() {
+        let spellings = |input| {
+            tokens_with_trivia(input)
+                .into_iter()
+                .map(|token| token.span.literal)
+                .collect::<Vec<_>>()
+        };
+
+        // Blank lines collapse and leading newlines are dropped
+        assert_eq!(spellings("\n\n1\n\n\n2"), ["1", "\n", "2", ""]);
+        // A line ending in an operator or `{` continues
+        assert_eq!(spellings("1 +\n2"), ["1", "+", "2", ""]);
+        assert_eq!(spellings("{\n1\n}"), ["{", "1", "\n", "}", ""]);
+        // A line starting with an operator does not
+        assert_eq!(spellings("1\n+ 2"), ["1", "\n", "+", "2", ""]);
+        // Newlines inside parentheses are dropped, unless a block is open inside them
+        assert_eq!(spellings("(1\n)"), ["(", "1", ")", ""]);
+        assert_eq!(spellings("({1\n})"), ["(", "{", "1", "\n", "}", ")", ""]);
+    }
+
+    #[test]
+    fn identifiers_follow_uax_31() {
+        let (tokens, diagnostics) = lex("größe _x x1 日本 e\u{301}");
+        let identifiers: Vec<_> = tokens
+            .into_iter()
+            .filter_map(|token| match token.kind {
+                TokenKind::Identifier(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(identifiers, ["größe", "_x", "x1", "日本", "e\u{301}"]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn non_identifier_character_is_reported() {
+        let (tokens, diagnostics) = lex("a € b");
+
+        assert_eq!(tokens[2].kind, TokenKind::Bad);
+        assert_eq!(messages(&diagnostics), ["unexpected character `€`"]);
+        assert_eq!(diagnostics[0].span, TextSpan::new(2, 5, "€".to_string()));
+    }
+
+    #[test]
+    fn keywords_are_looked_up_by_exact_spelling() {
+        for (spelling, kind) in KEYWORDS {
+            assert_eq!(lex(spelling).0[0].kind, kind);
+        }
+
+        for identifier in ["letter", "Let", "iff", "fn_"] {
+            assert_eq!(
+                lex(identifier).0[0].kind,
+                TokenKind::Identifier(identifier.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn eof_is_an_empty_span_at_the_end_of_input() {
+        for input in ["", "1 + 2", "\"é\" // ü\n"] {
+            let (tokens, _) = lex(input);
+            let eof = tokens.last().expect("Lexing always ends with Eof");
+
+            assert_eq!(eof.kind, TokenKind::Eof);
+            assert_eq!(
+                eof.span,
+                TextSpan::new(input.len(), input.len(), String::new())
+            );
+        }
+    }
+
+    #[test]
+    fn lexer_stops_after_eof() {
+        let mut lexer = Lexer::new("1", new_bag());
+
+        assert_eq!(
+            lexer.next().map(|token| token.kind),
+            Some(TokenKind::Number(1))
+        );
+        assert_eq!(lexer.next().map(|token| token.kind), Some(TokenKind::Eof));
+        assert_eq!(lexer.next(), None);
+        assert_eq!(lexer.next(), None);
+    }
+
+    /// Lexing a 4 MB input should take about four times as long as a 1 MB one.
+    /// Timings are too noisy for the normal test run, so run this with
+    /// `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore = "timing test, run in release with --ignored"]
+    fn lexing_time_is_linear_in_input_size() {
+        const MEGABYTE: usize = 1 << 20;
+        let line = "let größe = 0x1F + 2.5 * a /* note */ // \"é\"\n";
+
+        let time = |size: usize| {
+            let input = line.repeat(size / line.len());
+            let start = Instant::now();
+            let (tokens, diagnostics) = lex(&input);
+            let elapsed = start.elapsed();
+
+            assert!(diagnostics.is_empty());
+            assert!(tokens.len() > size / line.len());
+            elapsed
+        };
+
+        let small = time(MEGABYTE);
+        let large = time(4 * MEGABYTE);
+
+        // Quadratic lexing would take sixteen times as long
+        assert!(
+            large < small * 8,
+            "1 MB took {small:?} but 4 MB took {large:?}"
+        );
     }
 }
@@ -1,8 +1,11 @@
 pub mod evaluator;
 pub mod lexer;
 pub mod parser;
+pub mod resolver;
 
 mod counter;
+#[cfg(test)]
+mod test_helpers;
 mod tree;
 
 pub use tree::*;
@@ -1,17 +1,50 @@
 // #![cfg_attr(debug_assertions, allow(dead_code))]
 
-use crate::ast::lexer::{Lexer, Token, TokenKind};
-use crate::ast::{Expression, Statement};
-use crate::diagnostics::BagCell;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::ast::lexer::{attach_trivia, Lexer, TextSpan, Token, TokenKind};
+use crate::ast::{
+    BlockExpression, ElseBranch, Expression, ExpressionKind, Statement, StatementKind,
+};
+use crate::diagnostics::{Bag, BagCell};
 
 use super::counter::Counter;
-use super::{BinaryOperator, BinaryOperatorKind};
+use super::{
+    AssignmentOperator, AssignmentOperatorKind, Associativity, BinaryOperator, BinaryOperatorKind,
+    UnaryOperator, UnaryOperatorKind,
+};
+
+/// Syntax errors reported before the parser gives up on the rest of the file.
+const MAX_ERRORS: usize = 100;
+
+/// How deeply statements and expressions may nest. Every pass over the tree
+/// recurses once per level, so deeper input is reported rather than parsed
+/// into a tree that would overflow the stack.
+const MAX_NESTING_DEPTH: usize = 256;
+
+/// How deep the tree may get once the operators of flat chains like
+/// `1 + 2 + 3` are counted too. Each operator folded into a chain puts the
+/// ones before it a level deeper, so chains get a limit of their own.
+const MAX_OPERATION_DEPTH: usize = 4 * MAX_NESTING_DEPTH;
+
+/// The host stack that parsing, and every pass over the tree, needs for the
+/// deepest tree the parser accepts, with room to spare in a debug build.
+pub const STACK_SIZE: usize = 16 * 1024 * 1024;
 
 #[derive(Default)]
 pub struct Parser {
+    source: String,
     tokens: Vec<Token>,
     current: Counter,
     bag: BagCell,
+    /// Set by the first error in a statement and cleared once the parser has
+    /// synchronised on the next one, so one mistake is only reported once.
+    is_panicking: Cell<bool>,
+    error_count: Cell<usize>,
+    nesting_depth: usize,
+    /// The deepest the tree has got below the binary expression being parsed.
+    deepest: usize,
 }
 
 // TODO: Refactor into implementing iterators
@@ -25,61 +58,446 @@ impl Iterator for Parser {
 
 impl Parser {
     pub fn from_input(input: &str, bag: BagCell) -> Self {
-        let tokens: Vec<_> = Lexer::new(input)
-            .filter(|t| t.kind != TokenKind::Whitespace)
-            .collect();
+        let tokens = attach_trivia(Lexer::new(input, Rc::clone(&bag)));
 
         Self {
+            source: input.to_string(),
             tokens,
             current: Counter::default(),
             bag,
+            is_panicking: Cell::new(false),
+            error_count: Cell::new(0),
+            nesting_depth: 0,
+            deepest: 0,
         }
     }
 
     pub fn next_statement(&mut self) -> Option<Statement> {
+        self.skip_to_statement();
+
+        // A `}` with no block to close
+        while self.current().kind == TokenKind::RightBrace {
+            let token = self.consume();
+            self.report(|bag| bag.report_expected_expression(token));
+            self.skip_to_statement();
+        }
+
         if self.current().kind == TokenKind::Eof {
             return None;
         }
 
-        Some(self.parse_statement())
+        if self.error_count.get() >= MAX_ERRORS {
+            self.bag.borrow_mut().report_too_many_errors(self.current());
+            return None;
+        }
+
+        Some(self.parse_terminated_statement())
+    }
+
+    /// Recover from an error in the previous statement, then skip empty
+    /// statements, as in `;;`.
+    fn skip_to_statement(&self) {
+        if self.is_panicking.get() {
+            self.synchronize();
+        }
+
+        while matches!(
+            self.current().kind,
+            TokenKind::Newline | TokenKind::Semicolon
+        ) {
+            self.consume();
+        }
+    }
+
+    fn parse_terminated_statement(&mut self) -> Statement {
+        let statement = self.parse_statement();
+
+        // Like a closing brace, a statement ending in a block needs nothing after it
+        if !ends_with_block(&statement) {
+            self.consume_terminator();
+        }
+
+        statement
+    }
+
+    /// Statements end at a `;`, a newline, the `}` closing their block or the
+    /// end of the file.
+    fn consume_terminator(&self) {
+        let token = self.current();
+
+        match token.kind {
+            TokenKind::Newline | TokenKind::Semicolon => {
+                self.consume();
+            }
+            TokenKind::RightBrace | TokenKind::Eof => {}
+            _ => self.report(|bag| bag.report_expected_terminator(token)),
+        }
+    }
+
+    /// Skip the rest of a statement that had an error, stopping at the next
+    /// statement boundary. Every skipped token is consumed, so nothing is read
+    /// twice and the parser always reaches `Eof`.
+    fn synchronize(&self) {
+        self.is_panicking.set(false);
+
+        // Blocks opened while skipping are skipped whole, so their statements
+        // and closing brace are not mistaken for boundaries
+        let mut open_braces = 0_usize;
+
+        loop {
+            let kind = &self.current().kind;
+
+            match kind {
+                TokenKind::Eof => break,
+                TokenKind::LeftBrace => open_braces += 1,
+                TokenKind::RightBrace if open_braces > 0 => open_braces -= 1,
+                _ if open_braces == 0 && is_statement_boundary(kind) => break,
+                _ => {}
+            }
+
+            self.consume();
+        }
+    }
+
+    /// Report a syntax error unless the current statement already has one.
+    /// Past `MAX_ERRORS` nothing more is reported, even within a statement,
+    /// and `next_statement` gives up at the next chance.
+    fn report(&self, report: impl FnOnce(&mut Bag)) {
+        if self.is_panicking.replace(true) || self.error_count.get() >= MAX_ERRORS {
+            return;
+        }
+
+        self.error_count.set(self.error_count.get() + 1);
+        report(&mut self.bag.borrow_mut());
+    }
+
+    /// Go one level deeper into the tree, or report the current token and
+    /// return its span if that would be too deep. Callers restore
+    /// `nesting_depth` once they have built their node.
+    fn enter_nesting(&mut self) -> Result<(), TextSpan> {
+        if self.nesting_depth >= MAX_NESTING_DEPTH {
+            let token = self.current();
+            self.report(|bag| bag.report_nesting_too_deep(token, MAX_NESTING_DEPTH));
+            return Err(token.span.clone());
+        }
+
+        self.nesting_depth += 1;
+        self.deepest = self.deepest.max(self.nesting_depth);
+        Ok(())
+    }
+
+    /// Report the current operator and return its span if folding it into a
+    /// chain that already has `folded - 1` operators makes the tree too deep.
+    fn check_operation_depth(&self, folded: usize) -> Result<(), TextSpan> {
+        if self.deepest + folded > MAX_OPERATION_DEPTH {
+            let token = self.current();
+            self.report(|bag| bag.report_operations_too_deep(token, MAX_OPERATION_DEPTH));
+            return Err(token.span.clone());
+        }
+
+        Ok(())
     }
 
     fn parse_statement(&mut self) -> Statement {
-        let expr = self.parse_expression();
-        Statement::expression(expr)
+        if let Err(span) = self.enter_nesting() {
+            return Statement::expression(Expression::error(span));
+        }
+
+        let statement = match self.current().kind {
+            TokenKind::Let => self.parse_let_statement(),
+            TokenKind::LeftBrace => Statement::block(self.parse_block()),
+            TokenKind::While => self.parse_while_statement(),
+            TokenKind::For => self.parse_for_statement(),
+            // `fn(` starts an anonymous function, which is an expression
+            TokenKind::Fn if self.peek(1).kind != TokenKind::LeftParen => {
+                self.parse_function_declaration()
+            }
+            TokenKind::Return => self.parse_return_statement(),
+            TokenKind::Break => Statement::break_statement(self.consume().clone()),
+            TokenKind::Continue => Statement::continue_statement(self.consume().clone()),
+            TokenKind::Identifier(_) if self.parse_assignment_operator(1).is_some() => {
+                self.parse_assignment_statement()
+            }
+            _ => {
+                let expr = self.parse_expression();
+                Statement::expression(expr)
+            }
+        };
+
+        self.nesting_depth -= 1;
+        statement
+    }
+
+    fn parse_assignment_statement(&mut self) -> Statement {
+        let identifier = self.consume_and_check_identifier().clone();
+        let operator = self
+            .parse_assignment_operator(0)
+            .expect("Assignment statements are only parsed after an assignment operator");
+        self.consume();
+        let value = self.parse_expression();
+
+        Statement::assignment(identifier, operator, value)
+    }
+
+    fn parse_assignment_operator(&self, offset: isize) -> Option<AssignmentOperator> {
+        let token = self.peek(offset);
+
+        let kind = match token.kind {
+            TokenKind::Equals => Some(AssignmentOperatorKind::Assign),
+            TokenKind::PlusEquals => Some(AssignmentOperatorKind::Add),
+            TokenKind::MinusEquals => Some(AssignmentOperatorKind::Subtract),
+            TokenKind::AsteriskEquals => Some(AssignmentOperatorKind::Multiply),
+            TokenKind::SlashEquals => Some(AssignmentOperatorKind::Divide),
+            TokenKind::ModEquals => Some(AssignmentOperatorKind::Mod),
+            _ => None,
+        };
+
+        kind.map(|kind| AssignmentOperator {
+            token: token.clone(),
+            kind,
+        })
+    }
+
+    fn parse_let_statement(&mut self) -> Statement {
+        let let_keyword = self.consume_and_check(&TokenKind::Let).clone();
+        let identifier = self.consume_and_check_identifier().clone();
+        self.consume_and_check(&TokenKind::Equals);
+        let initializer = self.parse_expression();
+
+        Statement::let_statement(let_keyword, identifier, initializer)
+    }
+
+    fn parse_while_statement(&mut self) -> Statement {
+        let while_keyword = self.consume_and_check(&TokenKind::While).clone();
+        let condition = self.parse_expression();
+        let body = self.parse_block();
+
+        Statement::while_statement(while_keyword, condition, body)
+    }
+
+    fn parse_for_statement(&mut self) -> Statement {
+        let for_keyword = self.consume_and_check(&TokenKind::For).clone();
+        let variable = self.consume_and_check_identifier().clone();
+        self.consume_and_check(&TokenKind::In);
+        let iterable = self.parse_expression();
+        let body = self.parse_block();
+
+        Statement::for_statement(for_keyword, variable, iterable, body)
+    }
+
+    fn parse_function_declaration(&mut self) -> Statement {
+        let fn_keyword = self.consume_and_check(&TokenKind::Fn).clone();
+        let identifier = self.consume_and_check_identifier().clone();
+
+        self.consume_and_check(&TokenKind::LeftParen);
+        let parameters = self.parse_separated(&TokenKind::RightParen, |parser| {
+            parser.consume_and_check_identifier().clone()
+        });
+
+        let body = self.parse_block();
+
+        Statement::function_declaration(fn_keyword, identifier, parameters, body)
+    }
+
+    /// An anonymous function, either `|a, b| expression` or `fn(a, b) { ... }`.
+    fn parse_closure_expression(&mut self) -> Expression {
+        let keyword = self.consume().kind.clone();
+        let parse_parameter = |parser: &mut Self| parser.consume_and_check_identifier().clone();
+
+        match keyword {
+            TokenKind::PipePipe => Expression::closure(Vec::new(), self.parse_expression()),
+            TokenKind::Pipe => {
+                let parameters = self.parse_separated(&TokenKind::Pipe, parse_parameter);
+                Expression::closure(parameters, self.parse_expression())
+            }
+            _ => {
+                self.consume_and_check(&TokenKind::LeftParen);
+                let parameters = self.parse_separated(&TokenKind::RightParen, parse_parameter);
+                Expression::closure(parameters, Expression::block(self.parse_block()))
+            }
+        }
+    }
+
+    fn parse_return_statement(&mut self) -> Statement {
+        let return_keyword = self.consume_and_check(&TokenKind::Return).clone();
+
+        let has_value = !matches!(
+            self.current().kind,
+            TokenKind::Newline | TokenKind::Semicolon | TokenKind::RightBrace | TokenKind::Eof
+        );
+        let value = has_value.then(|| self.parse_expression());
+
+        Statement::return_statement(return_keyword, value)
+    }
+
+    /// Parse items separated by commas up to and including `close`, allowing a
+    /// trailing comma.
+    fn parse_separated<T>(
+        &mut self,
+        close: &TokenKind,
+        mut parse_item: impl FnMut(&mut Self) -> T,
+    ) -> Vec<T> {
+        let mut items = Vec::new();
+
+        while self.current().kind != *close && self.current().kind != TokenKind::Eof {
+            items.push(parse_item(self));
+
+            if self.current().kind != *close {
+                self.consume_and_check(&TokenKind::Comma);
+            }
+
+            // A token that was not consumed would otherwise be parsed again forever
+            if self.is_panicking.get() {
+                self.recover_to(close);
+                return items;
+            }
+        }
+
+        self.consume_and_check(close);
+
+        items
+    }
+
+    /// After an error in a delimited list, skip past its `close` token so the
+    /// rest of the statement can still be parsed. Nested delimiters are skipped
+    /// whole, and a statement boundary outside them ends the search.
+    fn recover_to(&self, close: &TokenKind) {
+        let mut depth = 0_usize;
+
+        loop {
+            let kind = &self.current().kind;
+
+            match kind {
+                TokenKind::Eof => return,
+                _ if depth == 0 && kind == close => {
+                    self.consume();
+                    self.is_panicking.set(false);
+                    return;
+                }
+                TokenKind::LeftParen | TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightParen | TokenKind::RightBrace if depth > 0 => depth -= 1,
+                _ if depth == 0 && is_statement_boundary(kind) => return,
+                _ => {}
+            }
+
+            self.consume();
+        }
+    }
+
+    fn parse_block(&mut self) -> BlockExpression {
+        let open_brace = self.consume_and_check(&TokenKind::LeftBrace).clone();
+        let mut statements = Vec::new();
+
+        // Without its `{`, what follows is left for `synchronize` rather than
+        // parsed as the body, which would then be missing its `}` as well
+        if open_brace.kind != TokenKind::LeftBrace {
+            return BlockExpression {
+                open_brace: open_brace.clone(),
+                statements,
+                close_brace: open_brace,
+            };
+        }
+
+        loop {
+            self.skip_to_statement();
+
+            let is_finished = matches!(self.current().kind, TokenKind::RightBrace | TokenKind::Eof);
+            if is_finished || self.error_count.get() >= MAX_ERRORS {
+                break;
+            }
+
+            statements.push(self.parse_terminated_statement());
+        }
+
+        let close_brace = self.consume_and_check(&TokenKind::RightBrace).clone();
+
+        BlockExpression {
+            open_brace,
+            statements,
+            close_brace,
+        }
+    }
+
+    fn parse_if_expression(&mut self) -> Expression {
+        // An `else if` chain nests without going through another expression
+        if let Err(span) = self.enter_nesting() {
+            return Expression::error(span);
+        }
+
+        let if_keyword = self.consume_and_check(&TokenKind::If).clone();
+        let condition = self.parse_expression();
+        let then_branch = self.parse_block();
+
+        // `else` may start the line after the closing brace
+        if self.current().kind == TokenKind::Newline && self.peek(1).kind == TokenKind::Else {
+            self.consume();
+        }
+
+        let else_branch = if self.current().kind == TokenKind::Else {
+            let else_keyword = self.consume().clone();
+            let body = if self.current().kind == TokenKind::If {
+                self.parse_if_expression()
+            } else {
+                Expression::block(self.parse_block())
+            };
+
+            Some(ElseBranch {
+                else_keyword,
+                body: Box::new(body),
+            })
+        } else {
+            None
+        };
+
+        self.nesting_depth -= 1;
+        Expression::if_expression(if_keyword, condition, then_branch, else_branch)
     }
 
     fn parse_expression(&mut self) -> Expression {
         self.parse_binary_expression(0)
     }
 
-    fn parse_binary_expression(&mut self, precedence: u8) -> Expression {
-        let mut left = self.parse_primary_expression();
+    /// Precedence climbing: only operators binding at least as tightly as
+    /// `min_precedence` are folded into this expression.
+    fn parse_binary_expression(&mut self, min_precedence: u8) -> Expression {
+        let enclosing_deepest = std::mem::replace(&mut self.deepest, self.nesting_depth);
+        let mut folded = 0;
+        let mut left = self.parse_unary_expression();
 
         while let Some(operator) = self.parse_binary_operator() {
-            // Consume the operator
-            self.consume();
+            let precedence = operator.precedence();
 
-            // Get the precedence
-            let op_precedence = operator.precedence();
+            // Leave the operator for the caller to fold
+            if precedence < min_precedence {
+                break;
+            }
 
-            // Break if the operator has lower precedence
-            if op_precedence <= precedence {
-                println!("Precedence is lower than current, breaking");
+            // Each operator folded in puts `left` one level deeper, but the
+            // operands are parsed at this level, so the chain is not nesting
+            folded += 1;
+            if let Err(span) = self.check_operation_depth(folded) {
+                left = Expression::error(span);
                 break;
             }
 
-            // Parse the right hand side
-            let right = self.parse_binary_expression(op_precedence);
+            self.consume();
 
-            // Combine the left and right hand side
+            // Left associative operators must not take an operator of the same
+            // precedence on their right, so `10 - 2 - 3` is `(10 - 2) - 3`
+            let next_precedence = match operator.associativity() {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            };
+
+            let right = self.parse_binary_expression(next_precedence);
             left = Expression::binary(left, right, operator);
         }
 
+        self.deepest = enclosing_deepest.max(self.deepest + folded);
         left
     }
 
-    fn parse_binary_operator(&mut self) -> Option<BinaryOperator> {
+    fn parse_binary_operator(&self) -> Option<BinaryOperator> {
         let token = self.current();
 
         let kind = match token.kind {
@@ -88,6 +506,21 @@ impl Parser {
             TokenKind::Asterisk => Some(BinaryOperatorKind::Multiply),
             TokenKind::Slash => Some(BinaryOperatorKind::Divide),
             TokenKind::Mod => Some(BinaryOperatorKind::Mod),
+            TokenKind::EqualsEquals => Some(BinaryOperatorKind::Equals),
+            TokenKind::BangEquals => Some(BinaryOperatorKind::NotEquals),
+            TokenKind::LessThan => Some(BinaryOperatorKind::LessThan),
+            TokenKind::LessThanEquals => Some(BinaryOperatorKind::LessThanOrEquals),
+            TokenKind::GreaterThan => Some(BinaryOperatorKind::GreaterThan),
+            TokenKind::GreaterThanEquals => Some(BinaryOperatorKind::GreaterThanOrEquals),
+            TokenKind::AmpersandAmpersand => Some(BinaryOperatorKind::And),
+            TokenKind::PipePipe => Some(BinaryOperatorKind::Or),
+            TokenKind::Ampersand => Some(BinaryOperatorKind::BitwiseAnd),
+            TokenKind::Pipe => Some(BinaryOperatorKind::BitwiseOr),
+            TokenKind::Caret => Some(BinaryOperatorKind::BitwiseXor),
+            TokenKind::LessThanLessThan => Some(BinaryOperatorKind::ShiftLeft),
+            TokenKind::GreaterThanGreaterThan => Some(BinaryOperatorKind::ShiftRight),
+            TokenKind::DotDot => Some(BinaryOperatorKind::Range),
+            TokenKind::DotDotEquals => Some(BinaryOperatorKind::RangeInclusive),
             _ => None,
         };
 
@@ -98,48 +531,483 @@ impl Parser {
         })
     }
 
+    /// Unary operators bind tighter than every binary operator, so `-2 * 3` is `(-2) * 3`.
+    fn parse_unary_expression(&mut self) -> Expression {
+        if let Err(span) = self.enter_nesting() {
+            return Expression::error(span);
+        }
+
+        let expr = match self.parse_unary_operator() {
+            Some(operator) => {
+                self.consume();
+                let operand = self.parse_unary_expression();
+                Expression::unary(operator, operand)
+            }
+            None => self.parse_call_expression(),
+        };
+
+        self.nesting_depth -= 1;
+        expr
+    }
+
+    /// Calls bind tighter than unary operators, so `-f(1)` is `-(f(1))`.
+    fn parse_call_expression(&mut self) -> Expression {
+        let start = self.current().span.start;
+        let depth = self.nesting_depth;
+        let mut expr = self.parse_primary_expression();
+
+        // A `(` after an expression that failed to parse is left for `synchronize`
+        while self.current().kind == TokenKind::LeftParen && !self.is_panicking.get() {
+            // Each call in a chain like `f()()` puts the callee one level deeper
+            if let Err(span) = self.enter_nesting() {
+                expr = Expression::error(span);
+                break;
+            }
+
+            self.consume();
+            let arguments = self.parse_separated(&TokenKind::RightParen, Self::parse_expression);
+            expr = Expression::call(expr, arguments, self.span_from(start));
+        }
+
+        self.nesting_depth = depth;
+        expr
+    }
+
+    fn parse_unary_operator(&self) -> Option<UnaryOperator> {
+        let token = self.current();
+
+        let kind = match token.kind {
+            TokenKind::Minus => Some(UnaryOperatorKind::Negate),
+            TokenKind::Plus => Some(UnaryOperatorKind::Identity),
+            TokenKind::Bang => Some(UnaryOperatorKind::Not),
+            TokenKind::Tilde => Some(UnaryOperatorKind::BitwiseNot),
+            _ => None,
+        };
+
+        kind.map(|kind| UnaryOperator {
+            token: token.clone(),
+            kind,
+        })
+    }
+
     fn parse_primary_expression(&mut self) -> Expression {
+        let token = self.current();
+
+        match token.kind {
+            TokenKind::LeftBrace => return Expression::block(self.parse_block()),
+            TokenKind::If => return self.parse_if_expression(),
+            TokenKind::Pipe | TokenKind::PipePipe => return self.parse_closure_expression(),
+            TokenKind::Fn if self.peek(1).kind == TokenKind::LeftParen => {
+                return self.parse_closure_expression();
+            }
+            _ => {}
+        }
+
+        // Leave the boundary for the statement it ends or starts
+        if is_statement_boundary(&token.kind) {
+            self.report(|bag| bag.report_expected_expression(token));
+            return Expression::error(token.span.clone());
+        }
+
         let token = self.consume();
 
         match token.kind {
-            TokenKind::Number(n) => Expression::number(n),
+            TokenKind::Number(n) => Expression::number(n, token.clone()),
+            TokenKind::Float(n) => Expression::float(n, token.clone()),
+            TokenKind::String(ref value) => Expression::string(value.clone(), token.clone()),
+            TokenKind::True => Expression::boolean(true, token.clone()),
+            TokenKind::False => Expression::boolean(false, token.clone()),
+            TokenKind::Identifier(_) => Expression::variable(token.clone()),
+            // The lexer has already reported why this token is bad
+            TokenKind::Bad => {
+                self.is_panicking.set(true);
+                Expression::error(token.span.clone())
+            }
             TokenKind::LeftParen => {
+                let open_paren = token.clone();
                 let expr = self.parse_expression();
-                self.consume_and_check(&TokenKind::RightParen);
+                let close_paren = self.consume_and_check(&TokenKind::RightParen).clone();
 
-                Expression::parenthesized(expr.kind)
+                Expression::parenthesized(open_paren, expr, close_paren)
             }
 
             _ => {
-                self.bag.borrow_mut().report_expected_expression(token);
+                self.report(|bag| bag.report_expected_expression(token));
                 Expression::error(token.span.clone())
             }
         }
     }
 
     fn peek(&self, offset: isize) -> &Token {
-        // let index = self.current.get() + offset as usize;
-        let index = self.current.wrapping_add_signed(offset) % self.tokens.len();
+        // Looking past the end keeps seeing `Eof` instead of wrapping around
+        let index = self
+            .current
+            .wrapping_add_signed(offset)
+            .min(self.tokens.len() - 1);
         self.tokens.get(index).expect("Out of bounds")
     }
 
+    /// The source from `start` to the end of the last consumed token.
+    fn span_from(&self, start: usize) -> TextSpan {
+        let end = self.peek(-1).span.end.max(start);
+        TextSpan::new(start, end, self.source[start..end].to_string())
+    }
+
     fn current(&self) -> &Token {
         self.peek(0)
     }
 
+    /// Consume the current token. `Eof` is never stepped past, so consuming at
+    /// the end of the file keeps returning it.
     fn consume(&self) -> &Token {
-        self.current.increment();
-        self.peek(-1)
+        let token = self.current();
+
+        if token.kind != TokenKind::Eof {
+            self.current.increment();
+        }
+
+        token
     }
 
+    /// Consume the current token if it is a `kind`. A mismatched token is
+    /// reported and left in place for `synchronize` to deal with.
     fn consume_and_check(&self, kind: &TokenKind) -> &Token {
-        let token = self.consume();
+        let token = self.current();
 
         if token.kind != *kind {
-            // eprintln!("Unexpected token: {token:?}");
-            self.bag.borrow_mut().report_unexpected_token(kind, token);
+            self.report(|bag| bag.report_unexpected_token(kind, token));
+            return token;
         }
 
-        token
+        self.consume()
+    }
+
+    fn consume_and_check_identifier(&self) -> &Token {
+        let token = self.current();
+
+        if !matches!(token.kind, TokenKind::Identifier(_)) {
+            self.report(|bag| bag.report_expected_identifier(token));
+            return token;
+        }
+
+        self.consume()
+    }
+}
+
+const fn ends_with_block(statement: &Statement) -> bool {
+    matches!(
+        statement.kind,
+        StatementKind::Block(_)
+            | StatementKind::While(_)
+            | StatementKind::For(_)
+            | StatementKind::Function(_)
+            | StatementKind::Expression(Expression {
+                kind: ExpressionKind::If(_)
+            })
+    )
+}
+
+/// Tokens that end a statement or can only begin one, where the parser
+/// synchronises after an error.
+const fn is_statement_boundary(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Newline
+            | TokenKind::Semicolon
+            | TokenKind::RightBrace
+            | TokenKind::Let
+            | TokenKind::While
+            | TokenKind::For
+            | TokenKind::Fn
+            | TokenKind::Return
+            | TokenKind::Break
+            | TokenKind::Continue
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{Parser, MAX_ERRORS, MAX_NESTING_DEPTH, MAX_OPERATION_DEPTH, STACK_SIZE};
+    use crate::ast::lexer::{Lexer, Token};
+    use crate::ast::test_helpers::{messages, new_bag, take_diagnostics, with_stack};
+    use crate::ast::{ExpressionKind, Statement, StatementKind};
+    use crate::diagnostics::Diagnostic;
+
+    /// Parse every statement in `input`, along with the diagnostics reported
+    /// while lexing and parsing.
+    fn parse(input: &str) -> (Vec<Statement>, Vec<Diagnostic>) {
+        let bag = new_bag();
+        let statements = Parser::from_input(input, Rc::clone(&bag)).collect();
+
+        (statements, take_diagnostics(&bag))
+    }
+
+    /// Parse `input` on a stack the size `main` parses on, returning the
+    /// number of statements and the message of every diagnostic.
+    fn parse_deep(input: String) -> (usize, Vec<String>) {
+        with_stack(STACK_SIZE, move || {
+            let (statements, diagnostics) = parse(&input);
+            let messages = messages(&diagnostics).into_iter().map(String::from);
+
+            (statements.len(), messages.collect())
+        })
+    }
+
+    #[test]
+    fn statements_end_at_newlines_and_semicolons() {
+        let (statements, diagnostics) = parse("let a = 1; let b = 2\na = a +\n    b\n(a\n + b)");
+
+        assert_eq!(statements.len(), 4);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn line_starting_with_an_operator_is_a_new_statement() {
+        let (statements, diagnostics) = parse("1\n- 2");
+
+        assert_eq!(statements.len(), 2);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn statements_on_one_line_need_a_separator() {
+        let (statements, diagnostics) = parse("let a = 1 let b = 2");
+
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            messages(&diagnostics),
+            ["expected `;` or newline, found <Let>"]
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_reported_instead_of_overflowing() {
+        const DEPTH: usize = 100_000;
+
+        let inputs = [
+            format!("{}1{}", "(".repeat(DEPTH), ")".repeat(DEPTH)),
+            format!("{}1", "-".repeat(DEPTH)),
+            format!("f{}", "()".repeat(DEPTH)),
+            format!("{}1{}", "{".repeat(DEPTH), "}".repeat(DEPTH)),
+            format!("{}1", "|| ".repeat(DEPTH)),
+            format!("if a {{}}{}", " else if a {}".repeat(DEPTH)),
+        ];
+        let expected = format!("code is nested more than {MAX_NESTING_DEPTH} levels deep");
+
+        for input in inputs {
+            let start = input[..20].to_string();
+            let (statements, messages) = parse_deep(input);
+
+            assert_eq!(statements, 1, "{start}");
+            assert_eq!(messages, [expected.as_str()], "{start}");
+        }
+    }
+
+    #[test]
+    fn long_operator_chains_are_not_nesting() {
+        let input = format!("1{}", " + 1".repeat(MAX_NESTING_DEPTH * 2));
+
+        assert!(parse(&input).1.is_empty());
+    }
+
+    #[test]
+    fn operator_chains_deeper_than_the_limit_are_reported() {
+        let expected = format!("expression is more than {MAX_OPERATION_DEPTH} operations deep");
+        let depth = MAX_OPERATION_DEPTH / 2;
+
+        for input in [
+            format!("1{}", " + 1".repeat(100_000)),
+            // Chains nested in the leftmost operand of another chain add up
+            format!(
+                "let a = (1{}){}",
+                " * 1".repeat(depth),
+                " + 1".repeat(depth)
+            ),
+            format!(
+                "{}1{}",
+                "(".repeat(128),
+                format!("{})", " + 1".repeat(8)).repeat(128)
+            ),
+        ] {
+            let start = input[..20].to_string();
+            let (statements, messages) = parse_deep(input);
+
+            assert_eq!(statements, 1, "{start}");
+            assert_eq!(messages, [expected.as_str()], "{start}");
+        }
+    }
+
+    #[test]
+    fn nesting_up_to_the_limit_is_accepted() {
+        let depth = MAX_NESTING_DEPTH / 2;
+        let input = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(parse(&input).1.is_empty());
+    }
+
+    #[test]
+    fn fn_without_name_or_parameters_is_reported_once() {
+        for (input, message) in [
+            ("fn", "Expected identifier, found <EOF>"),
+            ("fn 1", "Expected identifier, found <Number>"),
+            (
+                "let a = 1\nfn\nlet b = 2",
+                "Expected identifier, found <newline>",
+            ),
+        ] {
+            let (_, diagnostics) = parse(input);
+
+            assert_eq!(messages(&diagnostics), [message], "{input}");
+        }
+    }
+
+    #[test]
+    fn fn_in_expression_position_must_be_anonymous() {
+        let (statements, diagnostics) = parse("let f = fn(x) { x }\nf(fn() { 1 })");
+
+        assert_eq!(statements.len(), 2);
+        assert!(diagnostics.is_empty());
+
+        let (_, diagnostics) = parse("let f = fn g() { 1 }");
+
+        assert_eq!(messages(&diagnostics), ["Expected expression, found <fn>"]);
+    }
+
+    /// A xorshift generator, so every run fuzzes the same inputs.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+
+            usize::try_from(self.0 % bound as u64).expect("The result is below a usize")
+        }
+    }
+
+    /// Parse random token streams, checking that the parser always reaches the
+    /// end and that `MAX_ERRORS` caps its diagnostics. Errors from the lexer
+    /// are not capped, so they are counted on top.
+    #[test]
+    fn parser_terminates_on_random_tokens() {
+        const TOKENS: [&str; 58] = [
+            "let",
+            "fn",
+            "if",
+            "else",
+            "while",
+            "for",
+            "in",
+            "break",
+            "continue",
+            "return",
+            "true",
+            "false",
+            "a",
+            "b",
+            "f",
+            "(",
+            ")",
+            "(",
+            ")",
+            "{",
+            "}",
+            "{",
+            "}",
+            ",",
+            ";",
+            "\n",
+            "\n",
+            "=",
+            "+=",
+            "+",
+            "-",
+            "*",
+            "/",
+            "%",
+            "==",
+            "<",
+            "<<",
+            "&&",
+            "||",
+            "|",
+            "!",
+            "~",
+            "..",
+            "..=",
+            "1",
+            "2.5",
+            "0xff",
+            "\"s\"",
+            "$",
+            "/*",
+            "*/",
+            "// c\n",
+            "0x",
+            "1e",
+            "0b12",
+            "\"unterminated",
+            "é",
+            "\\",
+        ];
+
+        let mut rng = Rng(0x5eed);
+
+        for _ in 0..300 {
+            let length = rng.below(10_000);
+            let input = (0..length)
+                .map(|_| TOKENS[rng.below(TOKENS.len())])
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let lexer_bag = new_bag();
+            Lexer::new(&input, Rc::clone(&lexer_bag)).for_each(drop);
+            let lexer_errors = lexer_bag.borrow().diagnostics.len();
+
+            let bag = new_bag();
+            let count = Parser::from_input(&input, Rc::clone(&bag)).count();
+            let errors = bag.borrow().diagnostics.len();
+
+            assert!(count <= length, "{input:?}");
+            assert!(errors <= lexer_errors + MAX_ERRORS + 1, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn comments_stay_on_the_tokens_of_lets_booleans_and_parentheses() {
+        let literals = |trivia: &[Token]| -> Vec<String> {
+            trivia
+                .iter()
+                .map(|token| token.span.literal.clone())
+                .collect()
+        };
+
+        let (statements, diagnostics) =
+            parse("// doc\nlet a = ( /* open */ true /* value */ ) // close");
+        assert!(diagnostics.is_empty());
+
+        let StatementKind::Let(statement) = &statements[0].kind else {
+            panic!("expected a let statement");
+        };
+        let ExpressionKind::Parenthesized(parenthesized) = &statement.initializer.kind else {
+            panic!("expected a parenthesized expression");
+        };
+        let ExpressionKind::Boolean(boolean) = &parenthesized.expression.kind else {
+            panic!("expected a boolean");
+        };
+
+        assert_eq!(literals(&statement.let_keyword.leading_trivia), ["// doc"]);
+        assert_eq!(
+            literals(&parenthesized.open_paren.trailing_trivia),
+            ["/* open */"]
+        );
+        assert_eq!(literals(&boolean.token.trailing_trivia), ["/* value */"]);
+        assert_eq!(
+            literals(&parenthesized.close_paren.trailing_trivia),
+            ["// close"]
+        );
     }
 }
@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+
+use crate::diagnostics::BagCell;
+
+use super::{
+    lexer::{TextSpan, Token},
+    AssignmentStatement, Ast, BinaryExpression, BlockExpression, BooleanExpression, BreakStatement,
+    CallExpression, ClosureExpression, ContinueStatement, Expression, ExpressionKind,
+    FloatExpression, ForStatement, FunctionDeclaration, IfExpression, LetStatement,
+    NumberExpression, ParenthesizedExpression, ReturnStatement, StatementKind, StringExpression,
+    UnaryExpression, VariableExpression, Visitor, WhileStatement,
+};
+
+/// Walks the tree before evaluation and reports any variable that is used or
+/// assigned before it has been declared with `let` in the same or an enclosing
+/// block. Functions declared at the top level count as declared from the start.
+pub struct Resolver {
+    bag: BagCell,
+    /// The names declared in each open block, innermost last.
+    scopes: Vec<HashSet<String>>,
+    /// How many loops enclose the statement being resolved, within its function.
+    loop_depth: usize,
+    /// How many function bodies enclose the statement being resolved.
+    function_depth: usize,
+}
+
+impl Resolver {
+    pub fn new(bag: BagCell) -> Self {
+        Self {
+            bag,
+            scopes: vec![HashSet::new()],
+            loop_depth: 0,
+            function_depth: 0,
+        }
+    }
+
+    /// Resolve a whole program. Globals are looked up when a call runs, so
+    /// top-level functions are declared first and can call each other in any
+    /// order, as `Evaluator::evaluate` allows. Calling one before the `let`
+    /// of a global it reads is only caught by the evaluator, when it runs.
+    pub fn resolve(&mut self, ast: &Ast) {
+        for function in ast.functions() {
+            self.declare(function.name());
+        }
+
+        ast.visit(self);
+    }
+
+    fn declare(&mut self, name: &str) {
+        self.scopes
+            .last_mut()
+            .expect("The global scope is never popped")
+            .insert(name.to_string());
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// Resolve the body of a function or closure. Since it captures the scopes
+    /// around it, it sees them too, along with `names`, but not the loops.
+    fn resolve_function_body<'a>(
+        &mut self,
+        names: impl Iterator<Item = &'a Token>,
+        body: &Expression,
+    ) {
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.function_depth += 1;
+
+        let frame = names.map(|token| token.span.literal.clone()).collect();
+        self.scopes.push(frame);
+
+        self.visit_expression(body);
+
+        self.scopes.pop();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+    }
+
+    /// Report an `if` without `else` where `expr` must produce a value,
+    /// following blocks and both branches of an `if` to the expressions giving it.
+    fn check_has_value(&self, expr: &Expression) {
+        match &expr.kind {
+            ExpressionKind::If(expr) => match &expr.else_branch {
+                Some(else_branch) => {
+                    self.check_block_has_value(&expr.then_branch);
+                    self.check_has_value(&else_branch.body);
+                }
+                None => self.bag.borrow_mut().report_missing_else(&expr.if_keyword),
+            },
+            ExpressionKind::Block(block) => self.check_block_has_value(block),
+            ExpressionKind::Parenthesized(expr) => self.check_has_value(&expr.expression),
+            _ => {}
+        }
+    }
+
+    fn check_block_has_value(&self, block: &BlockExpression) {
+        if let Some(StatementKind::Expression(expr)) =
+            block.statements.last().map(|statement| &statement.kind)
+        {
+            self.check_has_value(expr);
+        }
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_let_statement(&mut self, statement: &LetStatement) {
+        // A closure bound to a global looks the global up when it is called,
+        // by which time the `let` has run, so it can call itself
+        let is_global_closure = self.scopes.len() == 1
+            && matches!(statement.initializer.kind, ExpressionKind::Closure(_));
+        if is_global_closure {
+            self.declare(statement.name());
+        }
+
+        // Otherwise the initializer is resolved first so `let a = a` is still an error.
+        self.check_has_value(&statement.initializer);
+        self.visit_expression(&statement.initializer);
+        self.declare(statement.name());
+    }
+
+    fn visit_assignment_statement(&mut self, statement: &AssignmentStatement) {
+        self.check_has_value(&statement.value);
+        self.visit_expression(&statement.value);
+
+        if !self.is_declared(statement.name()) {
+            self.bag
+                .borrow_mut()
+                .report_undeclared_assignment(&statement.identifier);
+        }
+    }
+
+    fn visit_while_statement(&mut self, statement: &WhileStatement) {
+        self.check_has_value(&statement.condition);
+        self.visit_expression(&statement.condition);
+
+        self.loop_depth += 1;
+        self.visit_block_expression(&statement.body);
+        self.loop_depth -= 1;
+    }
+
+    fn visit_for_statement(&mut self, statement: &ForStatement) {
+        self.check_has_value(&statement.iterable);
+        self.visit_expression(&statement.iterable);
+
+        self.scopes.push(HashSet::new());
+        self.declare(statement.name());
+
+        self.loop_depth += 1;
+        self.visit_block_expression(&statement.body);
+        self.loop_depth -= 1;
+
+        self.scopes.pop();
+    }
+
+    fn visit_function_declaration(&mut self, statement: &FunctionDeclaration) {
+        self.declare(statement.name());
+
+        let names = std::iter::once(&statement.identifier).chain(&statement.parameters);
+        self.resolve_function_body(names, &statement.body);
+    }
+
+    fn visit_return_statement(&mut self, statement: &ReturnStatement) {
+        if self.function_depth == 0 {
+            self.bag
+                .borrow_mut()
+                .report_outside_function(&statement.return_keyword);
+        }
+
+        if let Some(value) = &statement.value {
+            self.check_has_value(value);
+            self.visit_expression(value);
+        }
+    }
+
+    fn visit_break_statement(&mut self, statement: &BreakStatement) {
+        if self.loop_depth == 0 {
+            self.bag
+                .borrow_mut()
+                .report_outside_loop(&statement.keyword);
+        }
+    }
+
+    fn visit_continue_statement(&mut self, statement: &ContinueStatement) {
+        if self.loop_depth == 0 {
+            self.bag
+                .borrow_mut()
+                .report_outside_loop(&statement.keyword);
+        }
+    }
+
+    fn visit_number(&mut self, _number: &NumberExpression) {}
+
+    fn visit_float(&mut self, _float: &FloatExpression) {}
+
+    fn visit_string(&mut self, _string: &StringExpression) {}
+
+    fn visit_boolean(&mut self, _boolean: &BooleanExpression) {}
+
+    fn visit_unary_expression(&mut self, expr: &UnaryExpression) {
+        self.check_has_value(&expr.operand);
+        self.visit_expression(&expr.operand);
+    }
+
+    fn visit_binary_expression(&mut self, expr: &BinaryExpression) {
+        self.check_has_value(&expr.left);
+        self.check_has_value(&expr.right);
+        self.visit_expression(&expr.left);
+        self.visit_expression(&expr.right);
+    }
+
+    fn visit_parenthesized_expression(&mut self, expr: &ParenthesizedExpression) {
+        self.visit_expression(&expr.expression);
+    }
+
+    fn visit_variable_expression(&mut self, expr: &VariableExpression) {
+        if !self.is_declared(expr.name()) {
+            self.bag
+                .borrow_mut()
+                .report_undeclared_variable(&expr.identifier);
+        }
+    }
+
+    fn visit_block_expression(&mut self, block: &BlockExpression) {
+        self.scopes.push(HashSet::new());
+
+        for statement in &block.statements {
+            self.visit_statement(statement);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn visit_call_expression(&mut self, expr: &CallExpression) {
+        self.check_has_value(&expr.callee);
+        self.visit_expression(&expr.callee);
+
+        for argument in &expr.arguments {
+            self.check_has_value(argument);
+            self.visit_expression(argument);
+        }
+    }
+
+    fn visit_closure_expression(&mut self, expr: &ClosureExpression) {
+        self.resolve_function_body(expr.parameters.iter(), &expr.body);
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        self.check_has_value(&expr.condition);
+        self.visit_expression(&expr.condition);
+        self.visit_block_expression(&expr.then_branch);
+
+        if let Some(else_branch) = &expr.else_branch {
+            self.visit_expression(&else_branch.body);
+        }
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Resolver;
+    use crate::ast::test_helpers::{new_bag, parse_ast, take_diagnostics};
+
+    /// Parse and resolve `input`, returning the messages of every diagnostic.
+    fn resolve(input: &str) -> Vec<String> {
+        let bag = new_bag();
+        let ast = parse_ast(input, &bag);
+        assert!(!bag.borrow().has_errors(), "{input}");
+
+        Resolver::new(Rc::clone(&bag)).resolve(&ast);
+
+        take_diagnostics(&bag)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn block_variables_go_out_of_scope() {
+        assert_eq!(
+            resolve("{ let x = 1\nx }\nx"),
+            ["use of undeclared variable `x`"]
+        );
+        assert_eq!(
+            resolve("{ let x = 1 }\nx = 2"),
+            ["cannot assign to undeclared variable `x`"]
+        );
+    }
+
+    #[test]
+    fn outer_variables_are_visible_in_blocks() {
+        assert!(resolve("let x = 1\n{ { x = x + 1 } }").is_empty());
+    }
+
+    #[test]
+    fn if_without_else_used_as_a_value_is_reported() {
+        for input in [
+            "let a = if true { 1 }",
+            "let a = if true { 1 } else if false { 2 }",
+            "let a = { if true { 1 } }",
+            "let a = (if true { 1 })",
+            "let a = 1 + if true { 1 }",
+            "let a = if true { if false { 1 } } else { 2 }",
+        ] {
+            assert_eq!(
+                resolve(input),
+                ["`if` without `else` used as a value"],
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn if_without_else_as_a_statement_is_accepted() {
+        assert!(resolve("if true { 1 }\nlet a = { if true { 1 }\n2 }").is_empty());
+        assert!(resolve("let a = if true { 1 } else if false { 2 } else { 3 }").is_empty());
+    }
+
+    #[test]
+    fn break_and_continue_outside_a_loop_are_reported() {
+        assert_eq!(resolve("break"), ["`break` outside of a loop"]);
+        assert_eq!(resolve("{ continue }"), ["`continue` outside of a loop"]);
+        assert_eq!(
+            resolve("while true { fn f() { break } }"),
+            ["`break` outside of a loop"]
+        );
+    }
+
+    #[test]
+    fn break_and_continue_inside_a_loop_are_accepted() {
+        assert!(resolve("while true { if true { break } else { continue } }").is_empty());
+        assert!(resolve("for i in 0..3 { { break } }").is_empty());
+    }
+
+    #[test]
+    fn top_level_functions_can_be_called_before_their_declaration() {
+        assert!(resolve("fn a() { b() }\nfn b() { 1 }").is_empty());
+        assert!(resolve("let x = f()\nfn f() { 1 }").is_empty());
+    }
+
+    #[test]
+    fn local_functions_see_the_scopes_around_them() {
+        assert!(resolve("fn outer() { let x = 1\nfn a() { x }\nfn b() { a() }\nb() }").is_empty());
+        assert_eq!(
+            resolve("fn outer() { fn a() { b() }\nfn b() { 1 }\na() }"),
+            ["use of undeclared variable `b`"]
+        );
+    }
+
+    #[test]
+    fn closures_bound_to_globals_can_call_themselves() {
+        assert!(resolve("let f = |n| if n == 0 { 0 } else { f(n - 1) }").is_empty());
+        assert_eq!(resolve("let a = a"), ["use of undeclared variable `a`"]);
+        assert_eq!(
+            resolve("{ let f = |n| f(n) }"),
+            ["use of undeclared variable `f`"]
+        );
+    }
+}
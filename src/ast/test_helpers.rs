@@ -0,0 +1,45 @@
+//! Setup shared by the tests of every pass.
+
+use std::{cell::RefCell, rc::Rc};
+
+use super::{parser::Parser, Ast};
+use crate::diagnostics::{Bag, BagCell, Diagnostic};
+
+pub fn new_bag() -> BagCell {
+    Rc::new(RefCell::new(Bag::default()))
+}
+
+/// Parse every statement in `input` into a tree, reporting into `bag`.
+pub fn parse_ast(input: &str, bag: &BagCell) -> Ast {
+    let mut ast = Ast::default();
+    Parser::from_input(input, Rc::clone(bag)).for_each(|statement| {
+        ast.add_statement(statement);
+    });
+
+    ast
+}
+
+/// Take every diagnostic reported into `bag` so far.
+pub fn take_diagnostics(bag: &BagCell) -> Vec<Diagnostic> {
+    std::mem::take(&mut bag.borrow_mut().diagnostics)
+}
+
+/// Run `f` on a thread with `stack_size` bytes of stack, like `main` does.
+pub fn with_stack<T: Send + 'static>(
+    stack_size: usize,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    std::thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(f)
+        .expect("Failed to spawn a thread")
+        .join()
+        .expect("The thread panicked")
+}
+
+pub fn messages(diagnostics: &[Diagnostic]) -> Vec<&str> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect()
+}
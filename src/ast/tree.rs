@@ -1,3 +1,6 @@
+use std::fmt::Write;
+use std::rc::Rc;
+
 use termion::color::{self, Fg, Reset};
 
 use super::lexer::{TextSpan, Token};
@@ -12,6 +15,16 @@ impl Ast {
         self.statements.push(statement);
     }
 
+    /// The functions declared at the top level of the program.
+    pub fn functions(&self) -> impl Iterator<Item = &FunctionDeclaration> {
+        self.statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Function(function) => Some(function),
+                _ => None,
+            })
+    }
+
     pub fn visit(&self, visitor: &mut dyn Visitor) {
         for statement in &self.statements {
             visitor.visit_statement(statement);
@@ -29,35 +42,75 @@ pub trait Visitor {
     fn visit_statement(&mut self, statement: &Statement) {
         match &statement.kind {
             StatementKind::Expression(expr) => self.visit_expression(expr),
+            StatementKind::Let(statement) => self.visit_let_statement(statement),
+            StatementKind::Assignment(statement) => self.visit_assignment_statement(statement),
+            StatementKind::Block(block) => self.visit_block_expression(block),
+            StatementKind::While(statement) => self.visit_while_statement(statement),
+            StatementKind::For(statement) => self.visit_for_statement(statement),
+            StatementKind::Break(statement) => self.visit_break_statement(statement),
+            StatementKind::Continue(statement) => self.visit_continue_statement(statement),
+            StatementKind::Function(statement) => self.visit_function_declaration(statement),
+            StatementKind::Return(statement) => self.visit_return_statement(statement),
         }
     }
 
     fn visit_expression(&mut self, expression: &Expression) {
         match &expression.kind {
             ExpressionKind::Number(number) => self.visit_number(number),
+            ExpressionKind::Float(float) => self.visit_float(float),
+            ExpressionKind::String(string) => self.visit_string(string),
+            ExpressionKind::Boolean(boolean) => self.visit_boolean(boolean),
+            ExpressionKind::Unary(unary) => self.visit_unary_expression(unary),
             ExpressionKind::Binary(binary) => self.visit_binary_expression(binary),
             ExpressionKind::Parenthesized(parenthesized) => {
-                self.visit_parenthesized_expression(parenthesized)
+                self.visit_parenthesized_expression(parenthesized);
             }
+            ExpressionKind::Variable(variable) => self.visit_variable_expression(variable),
+            ExpressionKind::Block(block) => self.visit_block_expression(block),
+            ExpressionKind::If(expr) => self.visit_if_expression(expr),
+            ExpressionKind::Call(expr) => self.visit_call_expression(expr),
+            ExpressionKind::Closure(expr) => self.visit_closure_expression(expr),
             ExpressionKind::Error(span) => self.visit_error(span),
         }
     }
 
+    fn visit_let_statement(&mut self, statement: &LetStatement);
+    fn visit_assignment_statement(&mut self, statement: &AssignmentStatement);
+    fn visit_while_statement(&mut self, statement: &WhileStatement);
+    fn visit_for_statement(&mut self, statement: &ForStatement);
+    fn visit_break_statement(&mut self, statement: &BreakStatement);
+    fn visit_continue_statement(&mut self, statement: &ContinueStatement);
+    fn visit_function_declaration(&mut self, statement: &FunctionDeclaration);
+    fn visit_return_statement(&mut self, statement: &ReturnStatement);
     fn visit_number(&mut self, number: &NumberExpression);
+    fn visit_float(&mut self, float: &FloatExpression);
+    fn visit_string(&mut self, string: &StringExpression);
+    fn visit_boolean(&mut self, boolean: &BooleanExpression);
+    fn visit_unary_expression(&mut self, expr: &UnaryExpression);
     fn visit_binary_expression(&mut self, expr: &BinaryExpression);
     fn visit_parenthesized_expression(&mut self, expr: &ParenthesizedExpression);
+    fn visit_variable_expression(&mut self, expr: &VariableExpression);
+    fn visit_block_expression(&mut self, block: &BlockExpression);
+    fn visit_if_expression(&mut self, expr: &IfExpression);
+    fn visit_call_expression(&mut self, expr: &CallExpression);
+    fn visit_closure_expression(&mut self, expr: &ClosureExpression);
     fn visit_error(&mut self, expr: &TextSpan);
 }
 
+const INDENT_SIZE: usize = 4;
+
 #[derive(Default)]
 pub struct Printer {
-    // indent: usize,
+    indent: usize,
     result: String,
 }
 
 impl Printer {
     const NUMBER_COLOR: color::Cyan = color::Cyan;
     const TEXT_COLOR: color::White = color::White;
+    const KEYWORD_COLOR: color::Magenta = color::Magenta;
+    const VARIABLE_COLOR: color::Green = color::Green;
+    const STRING_COLOR: color::Yellow = color::Yellow;
 
     fn add_whitespace(&mut self) {
         self.result.push(' ');
@@ -83,62 +136,350 @@ impl Visitor for Printer {
     // }
 
     fn visit_statement(&mut self, statement: &Statement) {
+        let _ = write!(self.result, "{}", " ".repeat(self.indent));
+
         match &statement.kind {
             StatementKind::Expression(expr) => self.visit_expression(expr),
+            StatementKind::Let(statement) => self.visit_let_statement(statement),
+            StatementKind::Assignment(statement) => self.visit_assignment_statement(statement),
+            StatementKind::Block(block) => self.visit_block_expression(block),
+            StatementKind::While(statement) => self.visit_while_statement(statement),
+            StatementKind::For(statement) => self.visit_for_statement(statement),
+            StatementKind::Break(statement) => self.visit_break_statement(statement),
+            StatementKind::Continue(statement) => self.visit_continue_statement(statement),
+            StatementKind::Function(statement) => self.visit_function_declaration(statement),
+            StatementKind::Return(statement) => self.visit_return_statement(statement),
         }
 
-        self.result.push_str(&format!("{}", Fg(Reset)));
+        let _ = write!(self.result, "{}", Fg(Reset));
+        self.add_newline();
+    }
+
+    fn visit_let_statement(&mut self, statement: &LetStatement) {
+        let _ = write!(
+            self.result,
+            "{}let {}{}",
+            Fg(Self::KEYWORD_COLOR),
+            Fg(Self::VARIABLE_COLOR),
+            statement.identifier.span.literal,
+        );
+        self.add_whitespace();
+        let _ = write!(self.result, "{}=", Fg(Self::TEXT_COLOR));
+        self.add_whitespace();
+        self.visit_expression(&statement.initializer);
+    }
+
+    fn visit_assignment_statement(&mut self, statement: &AssignmentStatement) {
+        let _ = write!(
+            self.result,
+            "{}{}",
+            Fg(Self::VARIABLE_COLOR),
+            statement.identifier.span.literal,
+        );
+        self.add_whitespace();
+        let _ = write!(
+            self.result,
+            "{}{}",
+            Fg(Self::TEXT_COLOR),
+            statement.operator.token.span.literal
+        );
+        self.add_whitespace();
+        self.visit_expression(&statement.value);
+    }
+
+    fn visit_while_statement(&mut self, statement: &WhileStatement) {
+        let _ = write!(self.result, "{}while", Fg(Self::KEYWORD_COLOR));
+        self.add_whitespace();
+        self.visit_expression(&statement.condition);
+        self.add_whitespace();
+        self.visit_block_expression(&statement.body);
+    }
+
+    fn visit_for_statement(&mut self, statement: &ForStatement) {
+        let _ = write!(
+            self.result,
+            "{}for {}{} {}in",
+            Fg(Self::KEYWORD_COLOR),
+            Fg(Self::VARIABLE_COLOR),
+            statement.variable.span.literal,
+            Fg(Self::KEYWORD_COLOR),
+        );
+        self.add_whitespace();
+        self.visit_expression(&statement.iterable);
+        self.add_whitespace();
+        self.visit_block_expression(&statement.body);
+    }
+
+    fn visit_break_statement(&mut self, _statement: &BreakStatement) {
+        let _ = write!(self.result, "{}break", Fg(Self::KEYWORD_COLOR));
+    }
+
+    fn visit_continue_statement(&mut self, _statement: &ContinueStatement) {
+        let _ = write!(self.result, "{}continue", Fg(Self::KEYWORD_COLOR));
+    }
+
+    fn visit_function_declaration(&mut self, statement: &FunctionDeclaration) {
+        let parameters = statement
+            .parameters
+            .iter()
+            .map(|parameter| parameter.span.literal.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let _ = write!(
+            self.result,
+            "{}fn {}{}{}({}{}{})",
+            Fg(Self::KEYWORD_COLOR),
+            Fg(Self::VARIABLE_COLOR),
+            statement.identifier.span.literal,
+            Fg(Self::TEXT_COLOR),
+            Fg(Self::VARIABLE_COLOR),
+            parameters,
+            Fg(Self::TEXT_COLOR),
+        );
+        self.add_whitespace();
+        self.visit_expression(&statement.body);
+    }
+
+    fn visit_return_statement(&mut self, statement: &ReturnStatement) {
+        let _ = write!(self.result, "{}return", Fg(Self::KEYWORD_COLOR));
+
+        if let Some(value) = &statement.value {
+            self.add_whitespace();
+            self.visit_expression(value);
+        }
     }
 
     fn visit_number(&mut self, number: &NumberExpression) {
-        self.result.push_str(&format!(
+        let _ = write!(
+            self.result,
+            "{}{}",
+            color::Fg(Self::NUMBER_COLOR),
+            number.token.span.literal,
+        );
+    }
+
+    fn visit_float(&mut self, float: &FloatExpression) {
+        let _ = write!(
+            self.result,
             "{}{}",
             color::Fg(Self::NUMBER_COLOR),
-            number.number,
-        ));
+            float.token.span.literal,
+        );
+    }
+
+    fn visit_string(&mut self, string: &StringExpression) {
+        let _ = write!(
+            self.result,
+            "{}{}",
+            color::Fg(Self::STRING_COLOR),
+            string.token.span.literal,
+        );
+    }
+
+    fn visit_boolean(&mut self, boolean: &BooleanExpression) {
+        let _ = write!(self.result, "{}{}", Fg(Self::KEYWORD_COLOR), boolean.value);
     }
 
     fn visit_error(&mut self, span: &TextSpan) {
-        self.result
-            .push_str(&format!("{}{}", Fg(Self::TEXT_COLOR), span.literal));
+        let _ = write!(self.result, "{}{}", Fg(Self::TEXT_COLOR), span.literal);
+    }
+
+    fn visit_unary_expression(&mut self, expr: &UnaryExpression) {
+        let _ = write!(
+            self.result,
+            "{}{}",
+            color::Fg(Self::TEXT_COLOR),
+            expr.operator.token.span.literal
+        );
+        self.visit_expression(&expr.operand);
     }
 
     fn visit_binary_expression(&mut self, expr: &BinaryExpression) {
         self.visit_expression(&expr.left);
         self.add_whitespace();
-        self.result.push_str(&format!(
+        let _ = write!(
+            self.result,
             "{}{}",
             color::Fg(Self::TEXT_COLOR),
             expr.operator.token.span.literal
-        ));
+        );
         self.add_whitespace();
         self.visit_expression(&expr.right);
     }
 
     fn visit_parenthesized_expression(&mut self, expr: &ParenthesizedExpression) {
-        self.result
-            .push_str(&format!("{}(", color::Fg(Self::TEXT_COLOR)));
+        let _ = write!(self.result, "{}(", color::Fg(Self::TEXT_COLOR));
 
         self.visit_expression(&expr.expression);
 
-        self.result
-            .push_str(&format!("{})", color::Fg(Self::TEXT_COLOR)));
+        let _ = write!(self.result, "{})", color::Fg(Self::TEXT_COLOR));
+    }
+
+    fn visit_variable_expression(&mut self, expr: &VariableExpression) {
+        let _ = write!(
+            self.result,
+            "{}{}",
+            Fg(Self::VARIABLE_COLOR),
+            expr.identifier.span.literal
+        );
+    }
 
-        self.visit_expression(&expr.expression);
+    fn visit_block_expression(&mut self, block: &BlockExpression) {
+        let _ = write!(self.result, "{}{{", Fg(Self::TEXT_COLOR));
+        self.add_newline();
+
+        self.indent += INDENT_SIZE;
+        for statement in &block.statements {
+            self.visit_statement(statement);
+        }
+        self.indent -= INDENT_SIZE;
+
+        let _ = write!(
+            self.result,
+            "{}{}}}",
+            " ".repeat(self.indent),
+            Fg(Self::TEXT_COLOR)
+        );
     }
-}
 
-const INDENT_SIZE: usize = 2;
+    fn visit_call_expression(&mut self, expr: &CallExpression) {
+        self.visit_expression(&expr.callee);
+        let _ = write!(self.result, "{}(", Fg(Self::TEXT_COLOR));
 
-impl Printer {}
+        for (index, argument) in expr.arguments.iter().enumerate() {
+            if index > 0 {
+                let _ = write!(self.result, "{},", Fg(Self::TEXT_COLOR));
+                self.add_whitespace();
+            }
+
+            self.visit_expression(argument);
+        }
+
+        let _ = write!(self.result, "{})", Fg(Self::TEXT_COLOR));
+    }
+
+    fn visit_closure_expression(&mut self, expr: &ClosureExpression) {
+        let parameters = expr
+            .parameters
+            .iter()
+            .map(|parameter| parameter.span.literal.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let _ = write!(
+            self.result,
+            "{}|{}{}{}|",
+            Fg(Self::TEXT_COLOR),
+            Fg(Self::VARIABLE_COLOR),
+            parameters,
+            Fg(Self::TEXT_COLOR),
+        );
+        self.add_whitespace();
+        self.visit_expression(&expr.body);
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        let _ = write!(self.result, "{}if", Fg(Self::KEYWORD_COLOR));
+        self.add_whitespace();
+        self.visit_expression(&expr.condition);
+        self.add_whitespace();
+        self.visit_block_expression(&expr.then_branch);
+
+        if let Some(else_branch) = &expr.else_branch {
+            self.add_whitespace();
+            let _ = write!(self.result, "{}else", Fg(Self::KEYWORD_COLOR));
+            self.add_whitespace();
+            self.visit_expression(&else_branch.body);
+        }
+    }
+}
 
 // Statement
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum StatementKind {
     Expression(Expression),
+    Let(LetStatement),
+    Assignment(AssignmentStatement),
+    Block(BlockExpression),
+    While(WhileStatement),
+    For(ForStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
+    Function(FunctionDeclaration),
+    Return(ReturnStatement),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LetStatement {
+    pub let_keyword: Token,
+    pub identifier: Token,
+    pub initializer: Expression,
+}
+
+impl LetStatement {
+    pub fn name(&self) -> &str {
+        &self.identifier.span.literal
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WhileStatement {
+    pub while_keyword: Token,
+    pub condition: Expression,
+    pub body: BlockExpression,
+}
+
+/// `variable` takes each value of `iterable` in turn, in a scope of its own
+/// around `body`.
+#[derive(Debug, PartialEq)]
+pub struct ForStatement {
+    pub for_keyword: Token,
+    pub variable: Token,
+    pub iterable: Expression,
+    pub body: BlockExpression,
+}
+
+impl ForStatement {
+    pub fn name(&self) -> &str {
+        &self.variable.span.literal
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BreakStatement {
+    pub keyword: Token,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ContinueStatement {
+    pub keyword: Token,
+}
+
+/// The body is always a block, shared with every function value made from
+/// the declaration.
+#[derive(Debug, PartialEq)]
+pub struct FunctionDeclaration {
+    pub fn_keyword: Token,
+    pub identifier: Token,
+    pub parameters: Vec<Token>,
+    pub body: Rc<Expression>,
+}
+
+impl FunctionDeclaration {
+    pub fn name(&self) -> &str {
+        &self.identifier.span.literal
+    }
+}
+
+/// `value` is `None` for a bare `return`, which returns `()`.
+#[derive(Debug, PartialEq)]
+pub struct ReturnStatement {
+    pub return_keyword: Token,
+    pub value: Option<Expression>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Statement {
     pub kind: StatementKind,
 }
@@ -151,28 +492,262 @@ impl Statement {
     pub const fn expression(expression: Expression) -> Self {
         Self::new(StatementKind::Expression(expression))
     }
+
+    pub const fn let_statement(
+        let_keyword: Token,
+        identifier: Token,
+        initializer: Expression,
+    ) -> Self {
+        Self::new(StatementKind::Let(LetStatement {
+            let_keyword,
+            identifier,
+            initializer,
+        }))
+    }
+
+    pub const fn assignment(
+        identifier: Token,
+        operator: AssignmentOperator,
+        value: Expression,
+    ) -> Self {
+        Self::new(StatementKind::Assignment(AssignmentStatement {
+            identifier,
+            operator,
+            value,
+        }))
+    }
+
+    pub const fn block(block: BlockExpression) -> Self {
+        Self::new(StatementKind::Block(block))
+    }
+
+    pub const fn while_statement(
+        while_keyword: Token,
+        condition: Expression,
+        body: BlockExpression,
+    ) -> Self {
+        Self::new(StatementKind::While(WhileStatement {
+            while_keyword,
+            condition,
+            body,
+        }))
+    }
+
+    pub const fn for_statement(
+        for_keyword: Token,
+        variable: Token,
+        iterable: Expression,
+        body: BlockExpression,
+    ) -> Self {
+        Self::new(StatementKind::For(ForStatement {
+            for_keyword,
+            variable,
+            iterable,
+            body,
+        }))
+    }
+
+    pub fn function_declaration(
+        fn_keyword: Token,
+        identifier: Token,
+        parameters: Vec<Token>,
+        body: BlockExpression,
+    ) -> Self {
+        Self::new(StatementKind::Function(FunctionDeclaration {
+            fn_keyword,
+            identifier,
+            parameters,
+            body: Rc::new(Expression::block(body)),
+        }))
+    }
+
+    pub const fn return_statement(return_keyword: Token, value: Option<Expression>) -> Self {
+        Self::new(StatementKind::Return(ReturnStatement {
+            return_keyword,
+            value,
+        }))
+    }
+
+    pub const fn break_statement(keyword: Token) -> Self {
+        Self::new(StatementKind::Break(BreakStatement { keyword }))
+    }
+
+    pub const fn continue_statement(keyword: Token) -> Self {
+        Self::new(StatementKind::Continue(ContinueStatement { keyword }))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AssignmentStatement {
+    pub identifier: Token,
+    pub operator: AssignmentOperator,
+    pub value: Expression,
+}
+
+impl AssignmentStatement {
+    pub fn name(&self) -> &str {
+        &self.identifier.span.literal
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AssignmentOperatorKind {
+    Assign,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Mod,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssignmentOperator {
+    pub kind: AssignmentOperatorKind,
+    pub token: Token,
+}
+
+impl AssignmentOperator {
+    /// The binary operator a compound assignment applies, `None` for a plain `=`.
+    pub fn binary_operator(&self) -> Option<BinaryOperator> {
+        let kind = match self.kind {
+            AssignmentOperatorKind::Assign => None,
+            AssignmentOperatorKind::Add => Some(BinaryOperatorKind::Add),
+            AssignmentOperatorKind::Subtract => Some(BinaryOperatorKind::Subtract),
+            AssignmentOperatorKind::Multiply => Some(BinaryOperatorKind::Multiply),
+            AssignmentOperatorKind::Divide => Some(BinaryOperatorKind::Divide),
+            AssignmentOperatorKind::Mod => Some(BinaryOperatorKind::Mod),
+        };
+
+        kind.map(|kind| BinaryOperator {
+            kind,
+            token: self.token.clone(),
+        })
+    }
 }
 
 // Expression
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub enum ExpressionKind {
     Number(NumberExpression),
+    Float(FloatExpression),
+    String(StringExpression),
+    Boolean(BooleanExpression),
+    Unary(UnaryExpression),
     Binary(BinaryExpression),
     Parenthesized(ParenthesizedExpression),
+    Variable(VariableExpression),
+    Block(BlockExpression),
+    If(Box<IfExpression>),
+    Call(CallExpression),
+    Closure(ClosureExpression),
     Error(TextSpan),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct NumberExpression {
     pub number: i64,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FloatExpression {
+    pub number: f64,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StringExpression {
+    pub value: String,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BooleanExpression {
+    pub value: bool,
+    pub token: Token,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct ParenthesizedExpression {
+    pub open_paren: Token,
     pub expression: Box<Expression>,
+    pub close_paren: Token,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VariableExpression {
+    pub identifier: Token,
+}
+
+impl VariableExpression {
+    pub fn name(&self) -> &str {
+        &self.identifier.span.literal
+    }
+}
+
+/// Statements run in their own scope. The value is that of the final
+/// statement if it is an expression, otherwise `()`.
+#[derive(Debug, PartialEq)]
+pub struct BlockExpression {
+    pub open_brace: Token,
+    pub statements: Vec<Statement>,
+    pub close_brace: Token,
+}
+
+/// Without an `else` the value is `()` when the condition is false, so such an
+/// `if` can only be used as a statement.
+#[derive(Debug, PartialEq)]
+pub struct IfExpression {
+    pub if_keyword: Token,
+    pub condition: Box<Expression>,
+    pub then_branch: BlockExpression,
+    pub else_branch: Option<ElseBranch>,
+}
+
+/// `span` covers the whole call, from the start of the callee to the `)`.
+#[derive(Debug, PartialEq)]
+pub struct CallExpression {
+    pub callee: Box<Expression>,
+    pub arguments: Vec<Expression>,
+    pub span: TextSpan,
+}
+
+/// An anonymous function, written `|a, b| a + b` or `fn(a, b) { a + b }`.
+/// Evaluating it captures the local variables in scope by value.
+#[derive(Debug, PartialEq)]
+pub struct ClosureExpression {
+    pub parameters: Vec<Token>,
+    pub body: Rc<Expression>,
+}
+
+/// `body` is either a block or, for `else if`, another `if` expression.
+#[derive(Debug, PartialEq)]
+pub struct ElseBranch {
+    pub else_keyword: Token,
+    pub body: Box<Expression>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
+pub struct UnaryExpression {
+    pub operator: UnaryOperator,
+    pub operand: Box<Expression>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum UnaryOperatorKind {
+    Negate,
+    Identity,
+    Not,
+    BitwiseNot,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnaryOperator {
+    pub kind: UnaryOperatorKind,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq)]
 pub struct BinaryExpression {
     pub left: Box<Expression>,
     pub right: Box<Expression>,
@@ -186,24 +761,101 @@ pub enum BinaryOperatorKind {
     Multiply,
     Divide,
     Mod,
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    And,
+    Or,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
+    Range,
+    RangeInclusive,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct BinaryOperator {
     pub kind: BinaryOperatorKind,
     pub token: Token,
 }
 
-impl BinaryOperator {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Associativity {
+    Left,
+    // No right associative binary operators exist yet
+    #[allow(dead_code)]
+    Right,
+}
+
+impl BinaryOperatorKind {
+    /// Higher binds tighter, following C, with ranges below everything like in
+    /// Rust so `0..n + 1` is `0..(n + 1)`.
     pub const fn precedence(&self) -> u8 {
-        match self.kind {
-            BinaryOperatorKind::Add | BinaryOperatorKind::Subtract | BinaryOperatorKind::Mod => 1,
-            BinaryOperatorKind::Multiply | BinaryOperatorKind::Divide => 2,
+        match self {
+            Self::Range | Self::RangeInclusive => 0,
+            Self::Or => 1,
+            Self::And => 2,
+            Self::BitwiseOr => 3,
+            Self::BitwiseXor => 4,
+            Self::BitwiseAnd => 5,
+            Self::Equals | Self::NotEquals => 6,
+            Self::LessThan
+            | Self::LessThanOrEquals
+            | Self::GreaterThan
+            | Self::GreaterThanOrEquals => 7,
+            Self::ShiftLeft | Self::ShiftRight => 8,
+            Self::Add | Self::Subtract => 9,
+            Self::Multiply | Self::Divide | Self::Mod => 10,
         }
     }
+
+    /// Operators whose right operand is only evaluated when the left does not decide the result.
+    pub const fn is_short_circuiting(&self) -> bool {
+        matches!(self, Self::And | Self::Or)
+    }
+
+    pub const fn associativity(&self) -> Associativity {
+        match self {
+            Self::Add
+            | Self::Subtract
+            | Self::Multiply
+            | Self::Divide
+            | Self::Mod
+            | Self::Equals
+            | Self::NotEquals
+            | Self::LessThan
+            | Self::LessThanOrEquals
+            | Self::GreaterThan
+            | Self::GreaterThanOrEquals
+            | Self::And
+            | Self::Or
+            | Self::BitwiseAnd
+            | Self::BitwiseOr
+            | Self::BitwiseXor
+            | Self::ShiftLeft
+            | Self::ShiftRight
+            | Self::Range
+            | Self::RangeInclusive => Associativity::Left,
+        }
+    }
+}
+
+impl BinaryOperator {
+    pub const fn precedence(&self) -> u8 {
+        self.kind.precedence()
+    }
+
+    pub const fn associativity(&self) -> Associativity {
+        self.kind.associativity()
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Expression {
     pub kind: ExpressionKind,
 }
@@ -213,14 +865,33 @@ impl Expression {
         Self { kind }
     }
 
-    pub const fn number(number: i64) -> Self {
-        Self::new(ExpressionKind::Number(NumberExpression { number }))
+    pub const fn number(number: i64, token: Token) -> Self {
+        Self::new(ExpressionKind::Number(NumberExpression { number, token }))
+    }
+
+    pub const fn float(number: f64, token: Token) -> Self {
+        Self::new(ExpressionKind::Float(FloatExpression { number, token }))
+    }
+
+    pub const fn string(value: String, token: Token) -> Self {
+        Self::new(ExpressionKind::String(StringExpression { value, token }))
+    }
+
+    pub const fn boolean(value: bool, token: Token) -> Self {
+        Self::new(ExpressionKind::Boolean(BooleanExpression { value, token }))
     }
 
     pub const fn error(span: TextSpan) -> Self {
         Self::new(ExpressionKind::Error(span))
     }
 
+    pub fn unary(operator: UnaryOperator, operand: Self) -> Self {
+        Self::new(ExpressionKind::Unary(UnaryExpression {
+            operator,
+            operand: Box::new(operand),
+        }))
+    }
+
     pub fn binary(left: Self, right: Self, operator: BinaryOperator) -> Self {
         Self::new(ExpressionKind::Binary(BinaryExpression {
             left: Box::new(left),
@@ -229,9 +900,85 @@ impl Expression {
         }))
     }
 
-    pub fn parenthesized(kind: ExpressionKind) -> Self {
+    pub const fn variable(identifier: Token) -> Self {
+        Self::new(ExpressionKind::Variable(VariableExpression { identifier }))
+    }
+
+    pub const fn block(block: BlockExpression) -> Self {
+        Self::new(ExpressionKind::Block(block))
+    }
+
+    pub fn if_expression(
+        if_keyword: Token,
+        condition: Self,
+        then_branch: BlockExpression,
+        else_branch: Option<ElseBranch>,
+    ) -> Self {
+        Self::new(ExpressionKind::If(Box::new(IfExpression {
+            if_keyword,
+            condition: Box::new(condition),
+            then_branch,
+            else_branch,
+        })))
+    }
+
+    pub fn call(callee: Self, arguments: Vec<Self>, span: TextSpan) -> Self {
+        Self::new(ExpressionKind::Call(CallExpression {
+            callee: Box::new(callee),
+            arguments,
+            span,
+        }))
+    }
+
+    pub fn closure(parameters: Vec<Token>, body: Self) -> Self {
+        Self::new(ExpressionKind::Closure(ClosureExpression {
+            parameters,
+            body: Rc::new(body),
+        }))
+    }
+
+    pub fn parenthesized(open_paren: Token, expression: Self, close_paren: Token) -> Self {
         Self::new(ExpressionKind::Parenthesized(ParenthesizedExpression {
-            expression: Box::new(Self::new(kind)),
+            open_paren,
+            expression: Box::new(expression),
+            close_paren,
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Printer;
+    use crate::ast::test_helpers::{new_bag, parse_ast};
+
+    /// Parse `input` and print it back without the colour escape sequences.
+    fn print(input: &str) -> String {
+        let bag = new_bag();
+        let ast = parse_ast(input, &bag);
+        assert!(!bag.borrow().has_errors());
+
+        let mut printer = Printer::default();
+        ast.visit(&mut printer);
+
+        let mut result = String::new();
+        let mut chars = printer.result.chars();
+
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                result.push(c);
+            }
+        }
+
+        result
+    }
+
+    #[test]
+    fn number_literals_keep_their_spelling() {
+        assert_eq!(
+            print("let a = 0xFF + 0b1_0 * 1_000"),
+            "let a = 0xFF + 0b1_0 * 1_000\n"
+        );
+    }
+}
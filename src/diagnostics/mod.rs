@@ -1,11 +1,14 @@
 pub mod printer;
 
+use crate::ast::evaluator::OperatorError;
 use crate::ast::lexer::{TextSpan, Token, TokenKind};
 use std::{cell::RefCell, rc::Rc};
 
 pub type BagCell = Rc<RefCell<Bag>>;
 
 pub enum DiagnosticKind {
+    // No pass reports warnings yet
+    #[allow(dead_code)]
     Warning,
     Error,
 }
@@ -32,11 +35,18 @@ pub struct Bag {
 }
 
 impl Bag {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| matches!(diagnostic.kind, DiagnosticKind::Error))
+    }
+
     pub fn report_error(&mut self, message: String, span: TextSpan) {
         self.diagnostics
             .push(Diagnostic::new(message, span, DiagnosticKind::Error));
     }
 
+    #[allow(dead_code)]
     pub fn report_warning(&mut self, message: String, span: TextSpan) {
         self.diagnostics
             .push(Diagnostic::new(message, span, DiagnosticKind::Warning));
@@ -55,4 +65,199 @@ impl Bag {
             token.span.clone(),
         );
     }
+
+    pub fn report_expected_identifier(&mut self, token: &Token) {
+        self.report_error(
+            format!("Expected identifier, found <{}>", token.kind),
+            token.span.clone(),
+        );
+    }
+
+    pub fn report_expected_terminator(&mut self, token: &Token) {
+        self.report_error(
+            format!("expected `;` or newline, found <{}>", token.kind),
+            token.span.clone(),
+        );
+    }
+
+    pub fn report_too_many_errors(&mut self, token: &Token) {
+        self.report_error(
+            "too many syntax errors, giving up on the rest of the file".to_string(),
+            token.span.clone(),
+        );
+    }
+
+    pub fn report_nesting_too_deep(&mut self, token: &Token, max_depth: usize) {
+        self.report_error(
+            format!("code is nested more than {max_depth} levels deep"),
+            token.span.clone(),
+        );
+    }
+
+    pub fn report_operations_too_deep(&mut self, token: &Token, max_depth: usize) {
+        self.report_error(
+            format!("expression is more than {max_depth} operations deep"),
+            token.span.clone(),
+        );
+    }
+
+    pub fn report_missing_else(&mut self, if_keyword: &Token) {
+        self.report_error(
+            "`if` without `else` used as a value".to_string(),
+            if_keyword.span.clone(),
+        );
+    }
+
+    pub fn report_non_bool_condition(&mut self, keyword: &Token, type_name: &str) {
+        self.report_error(
+            format!("expected `bool` condition, found `{type_name}`"),
+            keyword.span.clone(),
+        );
+    }
+
+    pub fn report_outside_loop(&mut self, keyword: &Token) {
+        self.report_error(
+            format!("`{}` outside of a loop", keyword.span.literal),
+            keyword.span.clone(),
+        );
+    }
+
+    pub fn report_not_iterable(&mut self, for_keyword: &Token, type_name: &str) {
+        self.report_error(
+            format!("cannot iterate over `{type_name}`, expected a range"),
+            for_keyword.span.clone(),
+        );
+    }
+
+    pub fn report_outside_function(&mut self, keyword: &Token) {
+        self.report_error(
+            format!("`{}` outside of a function", keyword.span.literal),
+            keyword.span.clone(),
+        );
+    }
+
+    pub fn report_not_callable(&mut self, span: TextSpan, type_name: &str) {
+        self.report_error(format!("cannot call a value of type `{type_name}`"), span);
+    }
+
+    pub fn report_arity_mismatch(&mut self, span: TextSpan, expected: usize, found: usize) {
+        let plural = if expected == 1 { "" } else { "s" };
+
+        self.report_error(
+            format!("expected {expected} argument{plural}, found {found}"),
+            span,
+        );
+    }
+
+    pub fn report_stack_overflow(&mut self, span: TextSpan, max_call_depth: usize) {
+        self.report_error(
+            format!("stack overflow, calls are nested more than {max_call_depth} deep"),
+            span,
+        );
+    }
+
+    pub fn report_nested_too_deep_in_calls(&mut self, span: TextSpan, max_depth: usize) {
+        self.report_error(
+            format!("stack overflow, calls and the code in them are nested more than {max_depth} levels deep"),
+            span,
+        );
+    }
+
+    pub fn report_undeclared_variable(&mut self, identifier: &Token) {
+        self.report_error(
+            format!("use of undeclared variable `{}`", identifier.span.literal),
+            identifier.span.clone(),
+        );
+    }
+
+    pub fn report_used_before_let(&mut self, identifier: &Token) {
+        self.report_error(
+            format!("`{}` used before its `let` ran", identifier.span.literal),
+            identifier.span.clone(),
+        );
+    }
+
+    pub fn report_assigned_before_let(&mut self, identifier: &Token) {
+        self.report_error(
+            format!(
+                "`{}` assigned before its `let` ran",
+                identifier.span.literal
+            ),
+            identifier.span.clone(),
+        );
+    }
+
+    pub fn report_undeclared_assignment(&mut self, identifier: &Token) {
+        self.report_error(
+            format!(
+                "cannot assign to undeclared variable `{}`",
+                identifier.span.literal
+            ),
+            identifier.span.clone(),
+        );
+    }
+
+    pub fn report_bad_character(&mut self, character: char, span: TextSpan) {
+        self.report_error(format!("unexpected character `{character}`"), span);
+    }
+
+    pub fn report_invalid_digit(&mut self, digit: char, radix: u32, span: TextSpan) {
+        let base = match radix {
+            2 => "binary",
+            8 => "octal",
+            16 => "hexadecimal",
+            _ => "decimal",
+        };
+
+        self.report_error(format!("invalid digit `{digit}` in {base} literal"), span);
+    }
+
+    pub fn report_missing_digits(&mut self, span: TextSpan) {
+        self.report_error(format!("expected digits after `{}`", span.literal), span);
+    }
+
+    pub fn report_integer_literal_too_large(&mut self, span: TextSpan) {
+        self.report_error("integer literal too large".to_string(), span);
+    }
+
+    pub fn report_float_literal_too_large(&mut self, span: TextSpan) {
+        self.report_error("float literal too large".to_string(), span);
+    }
+
+    pub fn report_unterminated_string(&mut self, span: TextSpan) {
+        self.report_error("unterminated string literal".to_string(), span);
+    }
+
+    pub fn report_invalid_escape(&mut self, span: TextSpan) {
+        self.report_error(format!("invalid escape sequence `{}`", span.literal), span);
+    }
+
+    pub fn report_unterminated_block_comment(&mut self, span: TextSpan) {
+        self.report_error("unterminated block comment".to_string(), span);
+    }
+
+    pub fn report_operator_error(&mut self, operator: &Token, error: &OperatorError) {
+        let operator_literal = &operator.span.literal;
+
+        let message = match error {
+            OperatorError::InvalidOperand(operand) => {
+                format!("cannot apply `{operator_literal}` to `{operand}`")
+            }
+            OperatorError::InvalidOperands(left, right) => {
+                format!("cannot apply `{operator_literal}` to `{left}` and `{right}`")
+            }
+            OperatorError::DivisionByZero => "attempt to divide by zero".to_string(),
+            OperatorError::ModuloByZero => {
+                "attempt to calculate the remainder with a divisor of zero".to_string()
+            }
+            OperatorError::Overflow => {
+                format!("attempt to apply `{operator_literal}` with overflow")
+            }
+            OperatorError::ShiftOutOfRange(amount) => {
+                format!("shift amount `{amount}` is outside the range 0..64")
+            }
+        };
+
+        self.report_error(message, operator.span.clone());
+    }
 }
@@ -5,7 +5,7 @@ use termion::color::{Fg, Red, Reset};
 use crate::text;
 
 use super::Diagnostic;
-use std::cmp::{max, min};
+use std::cmp::min;
 
 pub struct Printer<'a> {
     text: &'a text::Source,
@@ -34,24 +34,23 @@ impl<'a> Printer<'a> {
         let column = diagnostic.span.start.saturating_sub(start);
         // let column = diagnostic.span.start - start;
 
-        let prefix_start = max(0, column as isize - PREFIX_LENGTH as isize) as usize;
-        let prefix_end = column;
+        // Spans are byte offsets, so every slice of the line is clamped and
+        // moved back onto a char boundary
+        let boundary = |index: usize| floor_char_boundary(line, min(index, line.len()));
 
-        let suffix_start = min(column + diagnostic.span.length(), line.len());
-        let suffix_end = min(suffix_start + PREFIX_LENGTH, line.len());
+        let prefix_start = boundary(column.saturating_sub(PREFIX_LENGTH));
+        let prefix_end = boundary(column);
+
+        let suffix_start = boundary(column + diagnostic.span.length());
+        let suffix_end = boundary(suffix_start + PREFIX_LENGTH);
 
         let prefix = &line[prefix_start..prefix_end];
         let span = &line[prefix_end..suffix_start];
         let suffix = &line[suffix_start..suffix_end];
 
-        let indent = max(PREFIX_LENGTH, column);
+        // Line the arrow up with the start of the highlighted span
+        let indent = prefix.chars().count();
 
-        let arrow_pointers = format!(
-            "{:indent$}{}",
-            "",
-            "│".repeat(diagnostic.span.length()),
-            indent = indent
-        );
         // let arrow_line = format!("{:indent$}│", "", indent = indent);
 
         let error_message = format!("{:indent$}└─ {}", "", diagnostic.message, indent = indent);
@@ -76,3 +75,11 @@ impl<'a> Printer<'a> {
         }
     }
 }
+
+const fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+
+    index
+}
@@ -8,26 +8,48 @@ use std::rc::Rc;
 use ast::parser::Parser;
 use ast::Ast;
 
-use crate::ast::evaluator::Evaluator;
+use crate::ast::evaluator::{self, Evaluator, DEFAULT_MAX_CALL_DEPTH, MAX_CALL_DEPTH};
+use crate::ast::resolver::Resolver;
 use crate::diagnostics::BagCell;
 
+/// The program run when no file is given.
+const DEMO: &str = "
+    let a = 10
+    let b = 20
+    let c = a + b
+";
+
 fn main() {
     // Check if user supplied a valid file
     let args: Vec<String> = std::env::args().collect();
 
     let input = args.get(1).map_or_else(
-        || {
-            String::from(
-                "
-                let a = 10
-                let b = 20
-                let c = a + b
-            ",
-            )
-        },
+        || String::from(DEMO),
         |file| std::fs::read_to_string(file).expect("Failed to read file"),
     );
 
+    // Recursion is limited so a runaway program reports a stack overflow
+    // instead of overflowing the interpreter's own stack
+    let max_call_depth = std::env::var("JAMD_MAX_CALL_DEPTH")
+        .ok()
+        .and_then(|depth| depth.parse().ok())
+        .map_or(DEFAULT_MAX_CALL_DEPTH, |depth: usize| {
+            depth.min(MAX_CALL_DEPTH)
+        });
+
+    // Every pass recurses over the tree, and evaluation over calls too, so
+    // run them on a stack sized for both, whatever the platform default
+    let interpreter = std::thread::Builder::new()
+        .stack_size(evaluator::stack_size(max_call_depth))
+        .spawn(move || run(input, max_call_depth))
+        .expect("Failed to spawn the interpreter thread");
+
+    if let Err(panic) = interpreter.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+fn run(input: String, max_call_depth: usize) {
     let diagnostics_bag: BagCell = Rc::new(RefCell::new(diagnostics::Bag::default()));
 
     let parser = Parser::from_input(&input, Rc::clone(&diagnostics_bag));
@@ -40,25 +62,37 @@ fn main() {
 
     ast.visualize();
 
+    let mut resolver = Resolver::new(Rc::clone(&diagnostics_bag));
+    resolver.resolve(&ast);
+
     let text = text::Source::new(input);
-    let diagnostics_binding = diagnostics_bag.borrow();
-    if !diagnostics_binding.diagnostics.is_empty() {
-        let diagnostics_printer =
-            diagnostics::printer::Printer::new(&text, &diagnostics_binding.diagnostics);
-        diagnostics_printer.print();
+    if print_diagnostics(&text, &diagnostics_bag) {
+        return;
     }
 
-    let mut evaluator = Evaluator::default();
-    ast.visit(&mut evaluator);
-    println!("\nStatement return value: {:?}", evaluator.last_value);
+    let mut evaluator =
+        Evaluator::new(Rc::clone(&diagnostics_bag)).with_max_call_depth(max_call_depth);
+    evaluator.evaluate(&ast);
 
-    // Print values nicer:
-    // let values = evaluator
-    //     .values
-    //     .iter()
-    //     .map(ToString::to_string)
-    //     .collect::<Vec<String>>()
-    //     .join(", ");
+    if print_diagnostics(&text, &diagnostics_bag) {
+        return;
+    }
+
+    if let Some(value) = evaluator.last_value {
+        println!("\nStatement return value: {value}");
+    }
+}
+
+/// Print and drain every diagnostic in the bag, returning whether any of them were errors.
+fn print_diagnostics(text: &text::Source, bag: &BagCell) -> bool {
+    let mut bag = bag.borrow_mut();
+    let has_errors = bag.has_errors();
+
+    let diagnostics = std::mem::take(&mut bag.diagnostics);
+    if !diagnostics.is_empty() {
+        let diagnostics_printer = diagnostics::printer::Printer::new(text, &diagnostics);
+        diagnostics_printer.print();
+    }
 
-    // println!("\nStatement return values: {values}");
+    has_errors
 }
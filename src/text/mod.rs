@@ -8,7 +8,7 @@ impl Source {
     }
 
     pub fn line_index(&self, position: usize) -> usize {
-        self.text[..position].lines().count() - 1
+        self.text[..position].matches('\n').count()
     }
 
     pub fn get_line(&self, index: usize) -> &str {